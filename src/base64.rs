@@ -0,0 +1,36 @@
+//! Decoding the standard base64 alphabet, for references inlined in test source.
+
+/// Decode base64 in the standard alphabet, ignoring whitespace, so long strings can be wrapped
+/// across lines, and with or without `=` padding.
+pub(crate) fn decode(encoded: &str) -> anyhow::Result<Vec<u8>> {
+    let digits: Vec<u8> = encoded.bytes().filter(|byte| !byte.is_ascii_whitespace()).collect();
+    let unpadded = digits
+        .strip_suffix(b"==")
+        .or_else(|| digits.strip_suffix(b"="))
+        .unwrap_or(&digits);
+    anyhow::ensure!(
+        unpadded.len() % 4 != 1,
+        "base64 of {} digits is truncated, as no whole number of bytes leaves a single digit over",
+        unpadded.len()
+    );
+
+    let mut bytes = Vec::with_capacity(unpadded.len() * 3 / 4);
+    let (mut buffer, mut bits) = (0_u32, 0);
+    for (index, &digit) in unpadded.iter().enumerate() {
+        let value = match digit {
+            b'A'..=b'Z' => digit - b'A',
+            b'a'..=b'z' => digit - b'a' + 26,
+            b'0'..=b'9' => digit - b'0' + 52,
+            b'+' => 62,
+            b'/' => 63,
+            _ => anyhow::bail!("`{}` at digit {} isn't a base64 digit", digit as char, index),
+        };
+        buffer = (buffer << 6) | value as u32;
+        bits += 6;
+        if bits >= 8 {
+            bits -= 8;
+            bytes.push((buffer >> bits) as u8);
+        }
+    }
+    Ok(bytes)
+}
@@ -0,0 +1,106 @@
+//! The bit depths images can be compared at.
+
+/// The bit depth both images are converted to before they are compared, for when the reference
+/// and the actual image were captured at different depths.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum BitDepth {
+    /// Convert both images to 8 bits per channel, rounding any 16-bit image down. Every
+    /// transformation in [`crate::Config`] and every [`crate::Metric`] works at this depth.
+    #[default]
+    Eight,
+    /// Convert both images to 16 bits per channel, spreading any 8-bit image out to the full
+    /// range, so no precision of a 16-bit image is lost.
+    /// Only [`crate::Metric::Ssim`], over 8x8 windows, and [`crate::Metric::Psnr`] can be
+    /// computed at this depth, and setting any option of [`crate::Config`] that transforms the
+    /// images before they are compared, like a mask or a crop, fails the comparison.
+    /// The SSIM here is computed differently from the one at 8 bits, so the same images score
+    /// differently at the two depths and thresholds need tuning for the one in use.
+    Sixteen,
+    /// Compare at 16 bits if the reference has more than 8 bits per channel, and at 8 bits
+    /// otherwise.
+    MatchReference,
+}
+
+impl BitDepth {
+    /// The depth to compare at against `expected`, the reference.
+    pub(crate) fn resolve(self, expected: &image::DynamicImage) -> BitDepth {
+        match self {
+            BitDepth::MatchReference => {
+                let color = expected.color();
+                if color.bytes_per_pixel() > color.channel_count() {
+                    BitDepth::Sixteen
+                } else {
+                    BitDepth::Eight
+                }
+            }
+            depth => depth,
+        }
+    }
+}
+
+/// Score `actual` against `expected` at 16 bits per channel, with the metric of the config.
+pub(crate) fn score_sixteen(
+    expected: &image::ImageBuffer<image::Rgba<u16>, Vec<u16>>,
+    actual: &image::ImageBuffer<image::Rgba<u16>, Vec<u16>>,
+    config: &crate::Config,
+) -> anyhow::Result<f64> {
+    if let Some(option) = unsupported_option(config) {
+        anyhow::bail!("`Config::{option}` can't be applied when comparing at 16 bits per channel");
+    }
+    crate::metric::ensure_same_size(expected.dimensions(), actual.dimensions())?;
+
+    match config.metric {
+        crate::Metric::Ssim => {
+            let normalize = |image: &image::ImageBuffer<image::Rgba<u16>, Vec<u16>>, x: u32, y: u32, channel: usize| {
+                image.get_pixel(x, y)[channel] as f64 / 65535.0
+            };
+            Ok(crate::metric::windowed_ssim(
+                expected.width(),
+                expected.height(),
+                |x, y, channel| normalize(expected, x, y, channel),
+                |x, y, channel| normalize(actual, x, y, channel),
+            ))
+        }
+        crate::Metric::Psnr => {
+            let squared_error: f64 = expected
+                .as_raw()
+                .iter()
+                .zip(actual.as_raw())
+                .map(|(&e, &a)| (e as f64 - a as f64).powi(2))
+                .sum();
+            let mse = squared_error / expected.as_raw().len().max(1) as f64;
+            if mse == 0.0 {
+                return Ok(f64::INFINITY);
+            }
+            Ok(10.0 * (65535.0_f64 * 65535.0 / mse).log10())
+        }
+        metric => anyhow::bail!("{metric:?} can't be computed at 16 bits per channel, only SSIM and PSNR can"),
+    }
+}
+
+/// The first option set in the config that transforms the images before they are compared, none
+/// of which are applied at 16 bits per channel.
+fn unsupported_option(config: &crate::Config) -> Option<&'static str> {
+    [
+        (!config.mask_regions.is_empty(), "mask_region"),
+        (config.autocrop.is_some(), "autocrop"),
+        (config.strip_letterbox, "strip_letterbox"),
+        (config.expected_scale.is_some(), "expected_scale"),
+        (config.auto_align.is_some(), "auto_align"),
+        (config.denoise_isolated.is_some(), "denoise_isolated"),
+        (config.edge_tolerance.is_some(), "edge_tolerance"),
+        (config.importance_map.is_some(), "importance_map"),
+        (config.reference_alpha_as_mask, "reference_alpha_as_mask"),
+        (config.ignore_border > 0, "ignore_border"),
+        (config.composite_over.is_some(), "composite_over"),
+        (config.ignore_alpha, "ignore_alpha"),
+        (config.linearize, "linearize"),
+        (config.ignore_transparent, "ignore_transparent"),
+        (config.premultiply_alpha, "premultiply_alpha"),
+        (config.color_space != crate::ColorSpace::Srgb, "color_space"),
+        (config.channel.is_some(), "channel"),
+        (config.focus_on_changes, "focus_on_changes"),
+    ]
+    .into_iter()
+    .find_map(|(set, option)| set.then_some(option))
+}
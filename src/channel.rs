@@ -0,0 +1,37 @@
+//! The channels images can be compared on in isolation.
+
+use image::Pixel;
+
+/// A single channel of an image, for comparing just that plane when the rest is irrelevant,
+/// e.g. checking a coverage mask through the alpha channel.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Channel {
+    /// The red channel.
+    Red,
+    /// The green channel.
+    Green,
+    /// The blue channel.
+    Blue,
+    /// The alpha channel.
+    Alpha,
+    /// The luminance of the color, ignoring alpha.
+    Luma,
+}
+
+impl Channel {
+    /// Copy the channel into the color of an opaque grayscale image, so any metric scores just
+    /// that channel.
+    pub(crate) fn isolate(self, image: &image::RgbaImage) -> image::RgbaImage {
+        image::RgbaImage::from_fn(image.width(), image.height(), |x, y| {
+            let pixel = image.get_pixel(x, y);
+            let value = match self {
+                Channel::Red => pixel[0],
+                Channel::Green => pixel[1],
+                Channel::Blue => pixel[2],
+                Channel::Alpha => pixel[3],
+                Channel::Luma => pixel.to_luma()[0],
+            };
+            image::Rgba([value, value, value, 255])
+        })
+    }
+}
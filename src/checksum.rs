@@ -0,0 +1,70 @@
+//! A sidecar file next to each reference holding a checksum of its contents, so a reference that
+//! was corrupted, e.g. truncated by a bad merge, fails clearly rather than with a confusing score.
+
+/// The environment variable that turns checksum sidecars on, when set to `1` or `true`.
+pub(crate) const CHECKSUMS_ENV_VAR: &str = "TWENTY_TWENTY_CHECKSUMS";
+
+/// Whether checksums should be written and verified.
+fn enabled() -> bool {
+    matches!(std::env::var(CHECKSUMS_ENV_VAR).as_deref(), Ok("1") | Ok("true"))
+}
+
+/// The path of the checksum sidecar of the reference at `path`.
+pub(crate) fn checksum_path(path: &std::path::Path) -> std::path::PathBuf {
+    path.with_extension("checksum")
+}
+
+/// The 64-bit FNV-1a hash of the bytes, formatted as hex.
+fn checksum(bytes: &[u8]) -> String {
+    let hash = bytes.iter().fold(0xcbf29ce484222325_u64, |hash, &byte| {
+        (hash ^ byte as u64).wrapping_mul(0x100000001b3)
+    });
+    format!("{hash:016x}")
+}
+
+/// Write the checksum sidecar of the reference at `path`, which has just been written.
+/// Does nothing unless `TWENTY_TWENTY_CHECKSUMS` is set.
+pub(crate) fn write(path: &std::path::Path) -> anyhow::Result<()> {
+    if !enabled() {
+        return Ok(());
+    }
+    let bytes = std::fs::read(path)?;
+    std::fs::write(checksum_path(path), checksum(&bytes) + "\n")
+        .map_err(|e| anyhow::anyhow!("unable to write checksum of {}: {}", path.display(), e))
+}
+
+/// Check the reference at `path` against its checksum sidecar.
+/// Does nothing unless `TWENTY_TWENTY_CHECKSUMS` is set, or if the reference or its sidecar
+/// doesn't exist yet.
+pub(crate) fn verify(path: &std::path::Path) -> anyhow::Result<()> {
+    if !enabled() {
+        return Ok(());
+    }
+    let Ok(expected) = std::fs::read_to_string(checksum_path(path)) else {
+        return Ok(());
+    };
+    let bytes = match std::fs::read(path) {
+        Ok(bytes) => bytes,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(()),
+        Err(e) => anyhow::bail!("unable to read contents of {}: {}", path.display(), e),
+    };
+    if checksum(&bytes) != expected.trim() {
+        anyhow::bail!(
+            "reference {} failed integrity check (corrupt or modified outside overwrite mode)",
+            path.display()
+        );
+    }
+    Ok(())
+}
+
+/// Rewrite the checksum sidecar of the reference at `path`, if it has one, for tools that accept
+/// new references outside of overwrite mode, like the `twenty-twenty` review binary.
+pub fn update_checksum<P: AsRef<std::path::Path>>(path: P) -> anyhow::Result<()> {
+    let path = path.as_ref();
+    if !checksum_path(path).exists() {
+        return Ok(());
+    }
+    let bytes = std::fs::read(path)?;
+    std::fs::write(checksum_path(path), checksum(&bytes) + "\n")
+        .map_err(|e| anyhow::anyhow!("unable to write checksum of {}: {}", path.display(), e))
+}
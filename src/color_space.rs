@@ -0,0 +1,107 @@
+//! The color spaces images can be compared in.
+
+/// The color space both images are converted to before they are compared.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum ColorSpace {
+    /// Compare the sRGB values as they are.
+    #[default]
+    Srgb,
+    /// Compare in CIELAB, where distances between colors are closer to how different they look,
+    /// so e.g. a hue shift in a brand color isn't scored as leniently as it is in sRGB.
+    /// With [`crate::Metric::Ssim`], each pixel scores the lower of the SSIM of its L* and one
+    /// minus its CIE76 Delta E over 100, the distance from black to white, so any visible change
+    /// of color counts, rather than only large ones.
+    Lab,
+}
+
+impl ColorSpace {
+    /// Convert the color of every pixel, which is in sRGB, into this color space, keeping alpha.
+    /// CIELAB is stored at 8 bits per channel, with L* scaled from 0..100 to 0..255 and a* and b*
+    /// offset by 128.
+    pub(crate) fn convert(self, image: &mut image::RgbaImage) {
+        match self {
+            ColorSpace::Srgb => {}
+            ColorSpace::Lab => {
+                for pixel in image.pixels_mut() {
+                    let [l, a, b] = srgb_to_lab([pixel[0], pixel[1], pixel[2]]);
+                    pixel[0] = (l * 2.55).round().clamp(0.0, 255.0) as u8;
+                    pixel[1] = (a + 128.0).round().clamp(0.0, 255.0) as u8;
+                    pixel[2] = (b + 128.0).round().clamp(0.0, 255.0) as u8;
+                }
+            }
+        }
+    }
+}
+
+/// The Delta E a pixel scores 0 at, the distance from black to white.
+const MAX_DELTA_E: f32 = 100.0;
+
+/// The similarity of each pixel of two images that have been converted to
+/// [`ColorSpace::Lab`], the lower of the SSIM of L* over the window it is in, one minus its
+/// Delta E over [`MAX_DELTA_E`], and the similarity of its alpha. Like the sRGB SSIM map,
+/// differences count less as the pixels get more transparent.
+pub(crate) fn lab_similarity_map(
+    expected: &image::RgbaImage,
+    actual: &image::RgbaImage,
+) -> anyhow::Result<crate::SimilarityMap> {
+    crate::metric::ensure_same_size(expected.dimensions(), actual.dimensions())?;
+    let (width, height) = expected.dimensions();
+    let expected_lightness = |x: u32, y: u32, _: usize| expected.get_pixel(x, y)[0] as f64 / 255.0;
+    let actual_lightness = |x: u32, y: u32, _: usize| actual.get_pixel(x, y)[0] as f64 / 255.0;
+    let lightness = crate::metric::windowed_map(width, height, |x0, y0| {
+        crate::metric::window_ssim(width, height, x0, y0, 0, &expected_lightness, &actual_lightness)
+    });
+
+    Ok(image::ImageBuffer::from_fn(
+        expected.width(),
+        expected.height(),
+        |x, y| {
+            let (e, a) = (expected.get_pixel(x, y), actual.get_pixel(x, y));
+            let unpack = |p: &image::Rgba<u8>| [p[0] as f32 / 2.55, p[1] as f32 - 128.0, p[2] as f32 - 128.0];
+            let (e_lab, a_lab) = (unpack(e), unpack(a));
+            let delta_e = e_lab
+                .iter()
+                .zip(a_lab)
+                .map(|(e, a)| (e - a).powi(2))
+                .sum::<f32>()
+                .sqrt();
+            let color = 1.0 - (delta_e / MAX_DELTA_E).min(1.0);
+            let alpha = 1.0 - (e[3] as f32 - a[3] as f32).abs() / 255.0;
+            let similarity = lightness.get_pixel(x, y)[0].clamp(0.0, 1.0).min(color).min(alpha);
+
+            let mean_alpha = (e[3] as f32 + a[3] as f32) / (2.0 * 255.0);
+            image::Luma([if mean_alpha > 0.0 {
+                (similarity / mean_alpha).clamp(0.0, 1.0)
+            } else {
+                1.0
+            }])
+        },
+    ))
+}
+
+/// Convert an sRGB color to CIELAB under the D65 white point.
+fn srgb_to_lab(rgb: [u8; 3]) -> [f32; 3] {
+    let [r, g, b] = rgb.map(|value| {
+        let value = value as f32 / 255.0;
+        if value <= 0.04045 {
+            value / 12.92
+        } else {
+            ((value + 0.055) / 1.055).powf(2.4)
+        }
+    });
+
+    // Relative to the D65 white point.
+    let x = (0.4124 * r + 0.3576 * g + 0.1805 * b) / 0.95047;
+    let y = 0.2126 * r + 0.7152 * g + 0.0722 * b;
+    let z = (0.0193 * r + 0.1192 * g + 0.9505 * b) / 1.08883;
+
+    let f = |t: f32| {
+        if t > 216.0 / 24389.0 {
+            t.cbrt()
+        } else {
+            (24389.0 / 27.0 * t + 16.0) / 116.0
+        }
+    };
+    let (fx, fy, fz) = (f(x), f(y), f(z));
+    [116.0 * fy - 16.0, 500.0 * (fx - fy), 200.0 * (fy - fz)]
+}
@@ -0,0 +1,715 @@
+//! Options for tweaking how images are compared.
+
+/// Options for tweaking how images are compared.
+///
+/// The defaults compare images exactly like [`crate::assert_image`] does.
+///
+/// ```rust
+/// # let actual = image::io::Reader::open("tests/dog1.png").unwrap().decode().unwrap();
+/// twenty_twenty::Config::new()
+///     .ignore_transparent(true)
+///     .assert_image("tests/dog1.png", &actual, 0.9);
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct Config {
+    pub(crate) ignore_transparent: bool,
+    pub(crate) ignore_alpha: bool,
+    pub(crate) premultiply_alpha: bool,
+    pub(crate) composite_over: Option<image::Rgb<u8>>,
+    pub(crate) require_reference: bool,
+    pub(crate) metric: crate::Metric,
+    pub(crate) diff_output: Option<std::path::PathBuf>,
+    pub(crate) prescreen: Option<u32>,
+    pub(crate) fast_fail: bool,
+    pub(crate) focus_on_changes: bool,
+    pub(crate) ignore_border: u32,
+    pub(crate) importance_map: Option<image::GrayImage>,
+    pub(crate) reference_alpha_as_mask: bool,
+    pub(crate) mask_regions: Vec<crate::Rect>,
+    pub(crate) linearize: bool,
+    pub(crate) channel: Option<crate::Channel>,
+    pub(crate) color_space: crate::ColorSpace,
+    pub(crate) bit_depth: crate::BitDepth,
+    pub(crate) auto_align: Option<u32>,
+    pub(crate) allow_rotations: bool,
+    pub(crate) expected_scale: Option<f64>,
+    pub(crate) label: Option<String>,
+    pub(crate) tags: Vec<String>,
+    pub(crate) denoise_isolated: Option<u32>,
+    pub(crate) compare_against_head: bool,
+    pub(crate) raw_reference: bool,
+    pub(crate) max_reference_bytes: Option<u64>,
+    pub(crate) autocrop: Option<image::Rgba<u8>>,
+    pub(crate) strip_letterbox: bool,
+    pub(crate) max_similarity: Option<f64>,
+    pub(crate) thresholds: Option<(f64, f64)>,
+    pub(crate) edge_tolerance: Option<u32>,
+    pub(crate) history_size: usize,
+    pub(crate) resample_filter: Option<image::imageops::FilterType>,
+    pub(crate) thumbnail_width: Option<u32>,
+    #[cfg(feature = "gif")]
+    pub(crate) flicker: bool,
+    #[cfg(feature = "exr")]
+    pub(crate) tonemap: bool,
+    #[cfg(feature = "h264")]
+    pub(crate) plane: crate::Plane,
+    #[cfg(feature = "h264")]
+    pub(crate) skip_frames: usize,
+}
+
+impl Config {
+    /// Create a new config with the default options.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Zero the color of fully transparent pixels in both images before comparing them, so
+    /// differences in color that can't be seen don't affect the score.
+    pub fn ignore_transparent(mut self, ignore_transparent: bool) -> Self {
+        self.ignore_transparent = ignore_transparent;
+        self
+    }
+
+    /// Make both images fully opaque before comparing them, keeping their color, for images
+    /// whose alpha channel is meaningless, like screenshots that come out with either opaque or
+    /// transparent alpha depending on how they were captured.
+    /// This takes alpha out of [`Config::ignore_transparent`] and [`Config::premultiply_alpha`]
+    /// too, as every pixel is opaque by the time they apply.
+    pub fn ignore_alpha(mut self, ignore_alpha: bool) -> Self {
+        self.ignore_alpha = ignore_alpha;
+        self
+    }
+
+    /// Alpha-composite both images over the `background` color before comparing them, so a
+    /// reference with transparency matches a capture of the same content rendered over that
+    /// color. Every pixel is opaque afterwards, so this takes alpha out of the other options too.
+    pub fn composite_over(mut self, background: image::Rgb<u8>) -> Self {
+        self.composite_over = Some(background);
+        self
+    }
+
+    /// Convert both images from straight to premultiplied alpha before comparing them, so they
+    /// are compared in the same space as a premultiplied-alpha pipeline would see them.
+    /// Premultiplying zeroes the color of fully transparent pixels, so this implies
+    /// [`Config::ignore_transparent`].
+    pub fn premultiply_alpha(mut self, premultiply_alpha: bool) -> Self {
+        self.premultiply_alpha = premultiply_alpha;
+        self
+    }
+
+    /// Fail when the reference file doesn't exist, rather than comparing against an empty image.
+    /// Overwrite mode still creates the reference.
+    /// This can also be turned on for every comparison with `TWENTY_TWENTY_STRICT=1`.
+    pub fn require_reference(mut self, require_reference: bool) -> Self {
+        self.require_reference = require_reference;
+        self
+    }
+
+    /// Score the images with the given metric rather than SSIM.
+    /// The threshold passed to the assertions is then in the units of that metric.
+    pub fn metric(mut self, metric: crate::Metric) -> Self {
+        self.metric = metric;
+        self
+    }
+
+    /// Write an image showing the expected image, the actual image, and a heatmap of their
+    /// differences side by side to this path after every comparison, whatever the
+    /// `TWENTY_TWENTY` mode.
+    pub fn diff_output<P: Into<std::path::PathBuf>>(mut self, path: P) -> Self {
+        self.diff_output = Some(path.into());
+        self
+    }
+
+    /// Compare downscaled copies of the images, fitting within `target_dimension` pixels, before
+    /// the full-resolution images.
+    /// If the downscaled images are obviously different, scoring below the minimum, or obviously
+    /// the same, scoring more than halfway from the minimum to a perfect score, that score is
+    /// used and the expensive full-resolution comparison is skipped.
+    /// Otherwise the score comes from the full-resolution comparison.
+    pub fn prescreen(mut self, target_dimension: u32) -> Self {
+        self.prescreen = Some(target_dimension);
+        self
+    }
+
+    /// Compare small thumbnails of the images by their mean difference in color first, and if
+    /// they are grossly different, fail straight away with that as an approximate score rather
+    /// than comparing them in full, to speed up suites where failures are common.
+    /// Images are only failed this way when the estimate is below both 0.5 and the minimum.
+    /// The estimate is made after the images are prepared, with masks, crops and the like
+    /// applied, and only for the [`crate::Metric::Ssim`], [`crate::Metric::MsSsim`] and
+    /// [`crate::Metric::Rms`] metrics on 8-bit images. It is skipped when
+    /// [`Config::allow_rotations`] is set.
+    pub fn fast_fail(mut self, fast_fail: bool) -> Self {
+        self.fast_fail = fast_fail;
+        self
+    }
+
+    /// Score only the bounding box of the pixels that differ, with a few pixels around it for
+    /// context, rather than the whole image, for huge images that mostly never change, where
+    /// scoring all of them is wasteful. The score reflects just that region, so a small change
+    /// scores much lower than it would across the whole image; set the threshold accordingly.
+    /// This happens after every other transformation.
+    pub fn focus_on_changes(mut self, focus_on_changes: bool) -> Self {
+        self.focus_on_changes = focus_on_changes;
+        self
+    }
+
+    /// Crop `pixels` off every edge of both images before comparing them, to ignore edge
+    /// artifacts like clamping or filtering that vary by platform.
+    /// Both images must be more than twice `pixels` wide and tall.
+    pub fn ignore_border(mut self, pixels: u32) -> Self {
+        self.ignore_border = pixels;
+        self
+    }
+
+    /// Weight how much each pixel counts towards the score by a grayscale importance map the
+    /// same size as the images, so the score is the mean of the SSIM of each pixel weighted by
+    /// the map. Black pixels of the map are ignored entirely, and a uniform map scores the same
+    /// as no map at all.
+    /// PSNR and RMS weight the squared error of each pixel the same way, while MS-SSIM and the
+    /// histogram can't be weighted, so they fail the comparison.
+    pub fn importance_map(mut self, importance_map: image::GrayImage) -> Self {
+        self.importance_map = Some(importance_map);
+        self
+    }
+
+    /// Only compare the pixels where the reference isn't fully transparent, using its alpha
+    /// channel as a mask of the region that matters, so whatever the actual image has outside
+    /// that shape can't affect the score. A reference that is entirely transparent fails the
+    /// comparison.
+    pub fn reference_alpha_as_mask(mut self, reference_alpha_as_mask: bool) -> Self {
+        self.reference_alpha_as_mask = reference_alpha_as_mask;
+        self
+    }
+
+    /// Ignore the differences within this region of the images, such as a clock or another
+    /// part of the UI that changes from run to run, by copying the reference's pixels over the
+    /// actual image's there. Several regions can be masked, by calling this once for each.
+    pub fn mask_region(mut self, region: crate::Rect) -> Self {
+        self.mask_regions.push(region);
+        self
+    }
+
+    /// Convert both images from sRGB to linear light before comparing them, which suits
+    /// physically-based renders better than comparing in sRGB, where highlights are
+    /// over-weighted.
+    /// The linear values are kept at 8 bits, so the very darkest sRGB values collapse together.
+    pub fn linearize(mut self, linearize: bool) -> Self {
+        self.linearize = linearize;
+        self
+    }
+
+    /// Alongside each artifact saved by the store-artifact modes, save a GIF that flips between
+    /// the expected and actual images every half second, named like the artifact with a
+    /// `.flicker.gif` extension. Flipping between them makes subtle shifts jump out far better
+    /// than a static diff.
+    #[cfg(feature = "gif")]
+    pub fn flicker(mut self, flicker: bool) -> Self {
+        self.flicker = flicker;
+        self
+    }
+
+    /// Tag the comparison, so it can be picked out with `TWENTY_TWENTY_ONLY`, e.g.
+    /// `TWENTY_TWENTY_ONLY=critical` makes every comparison not tagged `critical` pass without
+    /// being made. A comparison can carry several tags, by calling this once for each.
+    pub fn tag<S: Into<String>>(mut self, tag: S) -> Self {
+        self.tags.push(tag.into());
+        self
+    }
+
+    /// Put this label in the file name of the artifacts saved by the store-artifact modes, so
+    /// parameterized tests that compare against the same reference don't overwrite each other's
+    /// artifacts. The reference path is unchanged. See [`crate::artifact_path`] for the naming.
+    pub fn label<S: Into<String>>(mut self, label: S) -> Self {
+        self.label = Some(label.into());
+        self
+    }
+
+    /// Compare against the reference as it was committed at `git HEAD`, by running
+    /// `git show HEAD:<path>`, rather than the working tree copy, so a reference left modified
+    /// locally doesn't hide a regression. A reference that isn't committed yet is treated like a
+    /// missing one. Overwrite modes still write to the working tree.
+    pub fn compare_against_head(mut self, compare_against_head: bool) -> Self {
+        self.compare_against_head = compare_against_head;
+        self
+    }
+
+    /// Store the reference as its raw decoded RGBA8 bytes, rather than as a PNG, so it can't
+    /// drift with the version of the PNG encoder that wrote it. The file is the width and height
+    /// of the image as little-endian `u32`s followed by its pixels, row by row, so name it
+    /// something like `foo.rgba`.
+    /// Artifacts and images left pending are stored raw too, so the artifact reviewer can accept
+    /// them over the reference as they are.
+    pub fn raw_reference(mut self, raw_reference: bool) -> Self {
+        self.raw_reference = raw_reference;
+        self
+    }
+
+    /// Fail when overwriting a reference makes it larger than `max_reference_bytes`, e.g. to
+    /// stop an accepted high resolution render from bloating the repository. The reference is
+    /// still written, so it can be downscaled or recompressed before it is committed.
+    pub fn max_reference_bytes(mut self, max_reference_bytes: u64) -> Self {
+        self.max_reference_bytes = Some(max_reference_bytes);
+        self
+    }
+
+    /// Treat a differing pixel of the actual image as matching if the expected image has exactly
+    /// the same color within `radius` pixels of it, so thin features like one-pixel lines that
+    /// jittered by up to `radius` pixels still match.
+    /// Large radii will mask real differences, as most colors can be found somewhere nearby.
+    pub fn edge_tolerance(mut self, radius: u32) -> Self {
+        self.edge_tolerance = Some(radius);
+        self
+    }
+
+    /// Ignore differing pixels with fewer than `min_neighbours` differing pixels among their eight
+    /// neighbours, so a few stray speckles from nondeterministic dithering pass, while a real
+    /// structural change, like a shifted button, still fails.
+    pub fn denoise_isolated(mut self, min_neighbours: u32) -> Self {
+        self.denoise_isolated = Some(min_neighbours);
+        self
+    }
+
+    /// Crop both images to the bounding box of their content, the pixels that aren't exactly the
+    /// `background` color, before comparing them, so the same content with different padding
+    /// still matches. An image that is entirely background fails the comparison.
+    /// This happens before any other transformation, so an [`Config::importance_map`] has to
+    /// match the cropped size.
+    pub fn autocrop(mut self, background: image::Rgba<u8>) -> Self {
+        self.autocrop = Some(background);
+        self
+    }
+
+    /// Strip the bars of uniform color off the edges of both images before comparing them, for
+    /// captures that come out letterboxed or pillarboxed when the reference isn't, or the other
+    /// way around. The same number of rows are stripped off the top and bottom, and of columns
+    /// off the left and right. If the content left is a different size to the reference but in
+    /// proportion to it, the actual image is resampled to match, with
+    /// [`Config::resample_filter`].
+    /// This happens after [`Config::autocrop`] and before any other transformation.
+    pub fn strip_letterbox(mut self, strip_letterbox: bool) -> Self {
+        self.strip_letterbox = strip_letterbox;
+        self
+    }
+
+    /// Fail if the score is more than `max_similarity`, as well as if it is less than the
+    /// minimum, to check that an intentional change to a render actually took effect, e.g. that
+    /// a cache didn't serve the old render, by comparing against the old reference.
+    /// The threshold is in the units of the [`Config::metric`].
+    pub fn max_similarity(mut self, max_similarity: f64) -> Self {
+        self.max_similarity = Some(max_similarity);
+        self
+    }
+
+    /// Fail if the score is less than `fail`, in place of the `min_permissible_similarity` given
+    /// to the assertion, and pass with a warning on stderr if it is less than `warn`, so slowly
+    /// degrading references are noticed before they break the build. `warn` must be at least
+    /// `fail`. The minimum in a sidecar file next to the reference still takes precedence.
+    /// The thresholds are in the units of the [`Config::metric`].
+    pub fn thresholds(mut self, fail: f64, warn: f64) -> Self {
+        self.thresholds = Some((fail, warn));
+        self
+    }
+
+    /// Keep the last `history_size` accepted references and pass if the actual image matches any
+    /// of them, for renderers that nondeterministically produce one of a few valid outputs.
+    /// The newest reference stays at the path given, and the older ones are kept next to it as
+    /// `foo.1.png`, `foo.2.png` and so on, newest first. Overwrite mode shifts each of them back
+    /// one place, dropping the oldest, and saves the new reference at the path given.
+    pub fn history_size(mut self, history_size: usize) -> Self {
+        self.history_size = history_size;
+        self
+    }
+
+    /// Compare against references rendered at a different scale, e.g. `2.0` when the references
+    /// were captured at 1x but the actual images are rendered at 2x for a HiDPI display.
+    /// Whichever image is larger is resampled down to the size of the other, with
+    /// [`Config::resample_filter`], and they are compared at that common resolution.
+    /// The sizes must agree with the scale, to the nearest pixel.
+    pub fn expected_scale(mut self, scale: f64) -> Self {
+        self.expected_scale = Some(scale);
+        self
+    }
+
+    /// The filter used wherever images are resampled: by [`Config::expected_scale`],
+    /// [`Config::prescreen`], [`Config::thumbnail`], and the downsampling of
+    /// [`crate::Metric::MsSsim`]. It can noticeably change the scores, so pick the one that
+    /// matches your renderer's own resampling best.
+    /// Defaults to Lanczos3.
+    pub fn resample_filter(mut self, filter: image::imageops::FilterType) -> Self {
+        self.resample_filter = Some(filter);
+        self
+    }
+
+    /// Alongside each actual image the store-artifact modes save, save a copy scaled down to
+    /// `width` pixels wide as `<name>.thumb.png`, using the [`Config::resample_filter`], so a
+    /// large set of artifacts can be scanned quickly. Images narrower than `width` are saved as
+    /// they are.
+    pub fn thumbnail(mut self, width: u32) -> Self {
+        self.thumbnail_width = Some(width);
+        self
+    }
+
+    /// Pass if the actual image matches the reference once rotated a quarter, half, or three
+    /// quarter turn, printing the rotation that matched to stderr, for captures that come out
+    /// rotated, e.g. by a bug in their orientation metadata. Only the unrotated image is
+    /// compared unless it fails.
+    pub fn allow_rotations(mut self, allow_rotations: bool) -> Self {
+        self.allow_rotations = allow_rotations;
+        self
+    }
+
+    /// Find the translation, up to `max_shift` pixels in each direction, that best lines the
+    /// actual image up with the expected one by phase correlation, and shift it into place before
+    /// comparing them, to tolerate small registration differences while still catching changes
+    /// to the content.
+    /// Shifts that would leave less than half of the images overlapping aren't considered. The
+    /// strip of the image uncovered by the shift is ignored, and a non-zero shift is reported
+    /// along with a mismatch.
+    pub fn auto_align(mut self, max_shift: u32) -> Self {
+        self.auto_align = Some(max_shift);
+        self
+    }
+
+    /// Convert both images to this color space before comparing them, e.g.
+    /// [`crate::ColorSpace::Lab`] to weigh color differences closer to how different they look.
+    /// Converting to CIELAB takes the place of [`Config::linearize`].
+    pub fn color_space(mut self, color_space: crate::ColorSpace) -> Self {
+        self.color_space = color_space;
+        self
+    }
+
+    /// Convert both images to this bit depth before comparing them, e.g.
+    /// [`crate::BitDepth::Sixteen`] to keep the precision of a 16-bit capture compared against an
+    /// 8-bit reference, rather than rounding it down. Defaults to [`crate::BitDepth::Eight`].
+    /// Scores differ between the depths, so a threshold tuned at one doesn't carry over to the other.
+    pub fn compare_bit_depth(mut self, bit_depth: crate::BitDepth) -> Self {
+        self.bit_depth = bit_depth;
+        self
+    }
+
+    /// Compare only this channel of the images, as grayscale, ignoring the others.
+    /// This is applied after every other transformation, so e.g. premultiplying alpha still
+    /// affects the color channels.
+    pub fn channel(mut self, channel: crate::Channel) -> Self {
+        self.channel = Some(channel);
+        self
+    }
+
+    /// Tonemap floating point images into `[0, 1]` with the Reinhard operator before comparing
+    /// them, rather than normalizing them by their brightest value.
+    /// This only affects [`Config::assert_exr`].
+    #[cfg(feature = "exr")]
+    pub fn tonemap(mut self, tonemap: bool) -> Self {
+        self.tonemap = tonemap;
+        self
+    }
+
+    /// Which plane of decoded H.264 frames to compare: the frame converted to RGB, the default,
+    /// or only its luma, straight from the decoder, against a grayscale reference, to catch
+    /// regressions that don't involve chroma.
+    /// This only affects the H.264 assertions.
+    #[cfg(feature = "h264")]
+    pub fn plane(mut self, plane: crate::Plane) -> Self {
+        self.plane = plane;
+        self
+    }
+
+    /// Discard the first `skip_frames` decoded frames of H.264 streams and videos before picking
+    /// the frames to compare, for encoders that emit warm-up frames before the stream settles.
+    /// The frame index given to [`Config::assert_video_file_frame`] counts from after them, and
+    /// [`Config::assert_h264_keyframes`] only compares the keyframes that come after them.
+    /// This only affects the H.264 assertions.
+    #[cfg(feature = "h264")]
+    pub fn skip_frames(mut self, skip_frames: usize) -> Self {
+        self.skip_frames = skip_frames;
+        self
+    }
+
+    /// Compare the contents of the file to the image provided, using these options.
+    /// If the two are less similar than the `min_permissible_similarity` threshold,
+    /// the test will fail.
+    /// The `min_permissible_similarity` is a float between 0 and 1.
+    /// If the images are the exact same, the score will be 1.
+    #[track_caller]
+    pub fn assert_image<P: AsRef<std::path::Path>>(
+        &self,
+        path: P,
+        actual: &image::DynamicImage,
+        min_permissible_similarity: f64,
+    ) {
+        if let Err(e) = crate::assert_image_impl(path, actual, min_permissible_similarity, self) {
+            panic!("assertion failed: {e}")
+        }
+    }
+
+    /// Compare the contents of the file to the image provided, using these options, and return
+    /// the score on success. See [`crate::assert_image_scored`].
+    #[track_caller]
+    pub fn assert_image_scored<P: AsRef<std::path::Path>>(
+        &self,
+        path: P,
+        actual: &image::DynamicImage,
+        min_permissible_similarity: f64,
+    ) -> f64 {
+        match crate::assert_image_scored_impl(path, actual, min_permissible_similarity, self) {
+            Ok(score) => score,
+            Err(e) => panic!("assertion failed: {e}"),
+        }
+    }
+
+    /// Compare the contents of the file to the image provided, using these options, and return
+    /// the failure as an error instead of panicking. See [`crate::try_assert_image`].
+    pub fn try_assert_image<P: AsRef<std::path::Path>>(
+        &self,
+        path: P,
+        actual: &image::DynamicImage,
+        min_permissible_similarity: f64,
+    ) -> anyhow::Result<f64> {
+        crate::assert_image_scored_impl(path, actual, min_permissible_similarity, self)
+    }
+
+    /// Compare the image provided to a reference stored as a directory of tiles, using these
+    /// options. See [`crate::assert_image_tiled`].
+    #[track_caller]
+    pub fn assert_image_tiled<P: AsRef<std::path::Path>>(
+        &self,
+        dir: P,
+        actual: &image::DynamicImage,
+        min_permissible_similarity: f64,
+        tile_size: u32,
+    ) {
+        if let Err(e) =
+            crate::assert_image_tiled_impl(dir.as_ref(), actual, min_permissible_similarity, tile_size, self)
+        {
+            panic!("assertion failed: {e}")
+        }
+    }
+
+    /// Compare the contents of each file to its image, using these options, and fail listing
+    /// every comparison that failed. See [`crate::assert_images`].
+    #[track_caller]
+    pub fn assert_images<P: AsRef<std::path::Path>>(&self, cases: &[(P, &image::DynamicImage, f64)]) {
+        if let Err(e) = crate::assert_images_impl(cases, self) {
+            panic!("assertion failed: {e}")
+        }
+    }
+
+    /// Compare the contents of the file to the image provided, using these options, without
+    /// deciding whether the comparison passed. See [`crate::compare`].
+    /// [`Config::prescreen`] is ignored, as there is no threshold to screen against.
+    pub fn compare<P: AsRef<std::path::Path>>(
+        &self,
+        path: P,
+        actual: &image::DynamicImage,
+    ) -> anyhow::Result<crate::Comparison> {
+        crate::compare_impl(path.as_ref(), actual, self)
+    }
+
+    /// Compare two versions of a reference to each other, using these options.
+    /// See [`crate::diff_references`].
+    pub fn diff_references<P: AsRef<std::path::Path>, Q: AsRef<std::path::Path>>(
+        &self,
+        old: P,
+        new: Q,
+    ) -> anyhow::Result<crate::Comparison> {
+        crate::diff_references_impl(old.as_ref(), new.as_ref(), self)
+    }
+
+    /// Compare the contents of the file to the image provided, using these options, on tokio's
+    /// blocking thread pool so the file IO and comparison don't stall the async runtime.
+    /// See [`Config::assert_image`].
+    #[cfg(feature = "tokio")]
+    pub async fn assert_image_async<P: AsRef<std::path::Path>>(
+        &self,
+        path: P,
+        actual: &image::DynamicImage,
+        min_permissible_similarity: f64,
+    ) {
+        let path = path.as_ref().to_path_buf();
+        let actual = actual.clone();
+        let config = self.clone();
+        let result = tokio::task::spawn_blocking(move || {
+            crate::assert_image_impl(path, &actual, min_permissible_similarity, &config)
+        })
+        .await;
+        match result {
+            Ok(Ok(())) => {}
+            Ok(Err(e)) => panic!("assertion failed: {e}"),
+            Err(e) => panic!("comparison task failed: {e}"),
+        }
+    }
+
+    /// Compare the contents of the OpenEXR file to the floating point image provided, using
+    /// these options. See [`crate::assert_exr`].
+    /// If the two are less similar than the `min_permissible_similarity` threshold,
+    /// the test will fail.
+    /// The `min_permissible_similarity` is a float between 0 and 1.
+    /// If the images are the exact same, the score will be 1.
+    #[cfg(feature = "exr")]
+    #[track_caller]
+    pub fn assert_exr<P: AsRef<std::path::Path>>(
+        &self,
+        path: P,
+        actual: &image::Rgba32FImage,
+        min_permissible_similarity: f64,
+    ) {
+        if let Err(e) = crate::exr::assert_exr_impl(path.as_ref(), actual, min_permissible_similarity, self) {
+            panic!("assertion failed: {e}")
+        }
+    }
+
+    /// Compare the contents of the file to the H.264 frame provided, using these options.
+    /// If the two are less similar than the `min_permissible_similarity` threshold,
+    /// the test will fail.
+    /// The `min_permissible_similarity` is a float between 0 and 1.
+    /// If the images are the exact same, the score will be 1.
+    #[cfg(feature = "h264")]
+    #[track_caller]
+    pub fn assert_h264_frame<P: AsRef<std::path::Path>>(
+        &self,
+        path: P,
+        actual: &[u8],
+        min_permissible_similarity: f64,
+    ) {
+        // Don't bother decoding the frame if it won't be compared.
+        if self.skipped() {
+            return;
+        }
+        match crate::h264::decode_first_frame(actual, self.skip_frames, self.plane) {
+            Ok(image) => {
+                if let Err(e) = crate::assert_image_impl(path, &image, min_permissible_similarity, self) {
+                    panic!("assertion failed: {e}")
+                }
+            }
+            Err(e) => {
+                panic!("could not convert H.264 frame to image: {e}")
+            }
+        }
+    }
+
+    /// Compare the contents of the file to the first frame of the H.264 stream read from
+    /// `reader`, using these options. See [`crate::assert_h264_frame_reader`].
+    #[cfg(feature = "h264")]
+    #[track_caller]
+    pub fn assert_h264_frame_reader<P: AsRef<std::path::Path>, R: std::io::Read>(
+        &self,
+        path: P,
+        reader: R,
+        min_permissible_similarity: f64,
+    ) {
+        // Don't bother decoding the frame if it won't be compared.
+        if self.skipped() {
+            return;
+        }
+        match crate::h264::decode_first_frame_reader(reader, self.skip_frames, self.plane) {
+            Ok(image) => {
+                if let Err(e) = crate::assert_image_impl(path, &image, min_permissible_similarity, self) {
+                    panic!("assertion failed: {e}")
+                }
+            }
+            Err(e) => {
+                panic!("could not convert H.264 frame to image: {e}")
+            }
+        }
+    }
+
+    /// Compare frame `frame_index` of the video in the container file at `container_path` to
+    /// the reference image at `reference_path`, using these options. See
+    /// [`crate::assert_video_file_frame`].
+    #[cfg(feature = "h264")]
+    #[track_caller]
+    pub fn assert_video_file_frame<P: AsRef<std::path::Path>, Q: AsRef<std::path::Path>>(
+        &self,
+        container_path: P,
+        reference_path: Q,
+        min_permissible_similarity: f64,
+        frame_index: usize,
+    ) {
+        // Don't bother decoding the frame if it won't be compared.
+        if self.skipped() {
+            return;
+        }
+        match crate::h264::decode_file_frame(container_path.as_ref(), frame_index + self.skip_frames, self.plane) {
+            Ok(image) => {
+                if let Err(e) = crate::assert_image_impl(reference_path, &image, min_permissible_similarity, self) {
+                    panic!("assertion failed: {e}")
+                }
+            }
+            Err(e) => {
+                panic!("could not convert video frame to image: {e}")
+            }
+        }
+    }
+
+    /// Compare each frame of the H.264 clip provided to its own reference image, holding frame
+    /// `i` to `thresholds[i]`, using these options. See [`crate::assert_h264_frames`].
+    #[cfg(feature = "h264")]
+    #[track_caller]
+    pub fn assert_h264_frames(&self, path_pattern: &str, actual: &[u8], thresholds: &[f64]) {
+        if let Err(e) = crate::h264::assert_h264_frames_impl(path_pattern, actual, thresholds, self) {
+            panic!("assertion failed: {e}")
+        }
+    }
+
+    /// Compare every frame of the H.264 clip provided to the frames of the animated PNG (APNG)
+    /// file, using these options.
+    /// If the frame counts differ, or any frame is less similar than the
+    /// `min_permissible_similarity` threshold, the test will fail.
+    /// The `min_permissible_similarity` is a float between 0 and 1.
+    /// If the frames are the exact same, their score will be 1.
+    #[cfg(feature = "h264")]
+    #[track_caller]
+    pub fn assert_h264_animation<P: AsRef<std::path::Path>>(
+        &self,
+        path: P,
+        actual: &[u8],
+        min_permissible_similarity: f64,
+    ) {
+        if let Err(e) = crate::h264::assert_h264_animation_impl(path.as_ref(), actual, min_permissible_similarity, self)
+        {
+            panic!("assertion failed: {e}")
+        }
+    }
+
+    /// Compare only the keyframes of the H.264 clip provided to the frames of the animated PNG
+    /// (APNG) file, using these options. See [`crate::assert_h264_keyframes`].
+    #[cfg(feature = "h264")]
+    #[track_caller]
+    pub fn assert_h264_keyframes<P: AsRef<std::path::Path>>(
+        &self,
+        path: P,
+        actual: &[u8],
+        min_permissible_similarity: f64,
+    ) {
+        if let Err(e) = crate::h264::assert_h264_keyframes_impl(path.as_ref(), actual, min_permissible_similarity, self)
+        {
+            panic!("assertion failed: {e}")
+        }
+    }
+
+    /// The filter to resample images with.
+    pub(crate) fn filter(&self) -> image::imageops::FilterType {
+        self.resample_filter.unwrap_or(image::imageops::FilterType::Lanczos3)
+    }
+
+    /// Whether the comparison should be skipped, passing without being made, because
+    /// `TWENTY_TWENTY=off` or it carries none of the tags listed in `TWENTY_TWENTY_ONLY`.
+    pub(crate) fn skipped(&self) -> bool {
+        if crate::Mode::from_env() == crate::Mode::Off {
+            return true;
+        }
+        match std::env::var(crate::ONLY_ENV_VAR) {
+            Ok(only) if !only.trim().is_empty() => !only
+                .split(',')
+                .map(str::trim)
+                .any(|tag| self.tags.iter().any(|t| t == tag)),
+            _ => false,
+        }
+    }
+
+    /// Whether a missing reference should fail the comparison.
+    pub(crate) fn requires_reference(&self) -> bool {
+        self.require_reference || matches!(std::env::var(crate::STRICT_ENV_VAR).as_deref(), Ok("1") | Ok("true"))
+    }
+}
@@ -0,0 +1,94 @@
+//! Images that show how two images differ.
+
+/// Lay the expected image, the actual image, and a heatmap of their differences side by side.
+/// The heatmap is white where the images match and shades to red as the largest difference
+/// in any channel grows. The images must be the same size.
+pub(crate) fn triptych(expected: &image::RgbaImage, actual: &image::RgbaImage) -> image::RgbaImage {
+    let (width, height) = expected.dimensions();
+    let mut diff = image::RgbaImage::new(width * 3, height);
+    image::imageops::replace(&mut diff, expected, 0, 0);
+    image::imageops::replace(&mut diff, actual, width as i64, 0);
+    for (x, y, pixel) in heatmap(expected, actual).enumerate_pixels() {
+        diff.put_pixel(width * 2 + x, y, *pixel);
+    }
+    diff
+}
+
+/// Write a looping GIF to `path` that flips between the expected and actual images every half
+/// second, which makes subtle shifts jump out.
+#[cfg(feature = "gif")]
+pub(crate) fn write_flicker_gif(
+    expected: &image::RgbaImage,
+    actual: &image::RgbaImage,
+    path: &std::path::Path,
+) -> anyhow::Result<()> {
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    let file = std::fs::File::create(path)?;
+    let mut encoder = image::codecs::gif::GifEncoder::new(std::io::BufWriter::new(file));
+    let delay = image::Delay::from_numer_denom_ms(500, 1);
+    encoder
+        .set_repeat(image::codecs::gif::Repeat::Infinite)
+        .and_then(|()| {
+            encoder.encode_frames([expected, actual].map(|image| image::Frame::from_parts(image.clone(), 0, 0, delay)))
+        })
+        .map_err(|e| anyhow::anyhow!("unable to write image to {}: {}", path.display(), e))
+}
+
+/// The bounding box of the contiguous region around the least similar pixel of an SSIM map,
+/// taking in the pixels that are closer to it than to the mean similarity.
+pub(crate) fn worst_region(map: &crate::SimilarityMap) -> Option<crate::Rect> {
+    let (worst_x, worst_y, worst) = map
+        .enumerate_pixels()
+        .min_by(|(_, _, a), (_, _, b)| a[0].total_cmp(&b[0]))
+        .map(|(x, y, pixel)| (x, y, pixel[0]))?;
+    let mean = map.pixels().map(|p| p[0] as f64).sum::<f64>() / map.pixels().len() as f64;
+    let threshold = (worst as f64 + mean) / 2.0;
+
+    let (width, height) = map.dimensions();
+    let mut visited = vec![false; width as usize * height as usize];
+    let mut stack = vec![(worst_x, worst_y)];
+    visited[(worst_y * width + worst_x) as usize] = true;
+    let (mut min_x, mut min_y, mut max_x, mut max_y) = (worst_x, worst_y, worst_x, worst_y);
+    while let Some((x, y)) = stack.pop() {
+        min_x = min_x.min(x);
+        min_y = min_y.min(y);
+        max_x = max_x.max(x);
+        max_y = max_y.max(y);
+        let neighbours = [(x.wrapping_sub(1), y), (x + 1, y), (x, y.wrapping_sub(1)), (x, y + 1)];
+        for (x, y) in neighbours {
+            if x >= width || y >= height {
+                continue;
+            }
+            let index = (y * width + x) as usize;
+            if !visited[index] && map.get_pixel(x, y)[0] as f64 <= threshold {
+                visited[index] = true;
+                stack.push((x, y));
+            }
+        }
+    }
+
+    Some(crate::Rect {
+        x: min_x,
+        y: min_y,
+        width: max_x - min_x + 1,
+        height: max_y - min_y + 1,
+    })
+}
+
+/// A heatmap of the differences between the images, white where they match and shading to red
+/// as the largest difference in any channel grows.
+fn heatmap(expected: &image::RgbaImage, actual: &image::RgbaImage) -> image::RgbaImage {
+    image::RgbaImage::from_fn(expected.width(), expected.height(), |x, y| {
+        let e = expected.get_pixel(x, y);
+        let a = actual.get_pixel(x, y);
+        let difference =
+            e.0.iter()
+                .zip(a.0)
+                .map(|(&e, a)| e.abs_diff(a))
+                .max()
+                .unwrap_or_default();
+        image::Rgba([255, 255 - difference, 255 - difference, 255])
+    })
+}
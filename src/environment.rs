@@ -0,0 +1,54 @@
+//! A record of the environment an artifact was produced in, saved next to it, so a failure that
+//! only happens on some platforms can be traced to the one that produced the diverging output.
+
+use serde::Serialize;
+
+/// The environment an artifact was produced in.
+#[derive(Serialize)]
+struct Environment {
+    /// The operating system, like `linux` or `macos`.
+    os: &'static str,
+    /// The CPU architecture, like `x86_64` or `aarch64`.
+    arch: &'static str,
+    /// The version of this crate.
+    twenty_twenty: &'static str,
+    /// The version of libavcodec that decoded H.264 frames, with the `h264` feature.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    ffmpeg: Option<String>,
+}
+
+/// The path of the environment record of the artifact at `path`, so `foo.png` has its record in
+/// `foo.meta.json`.
+pub(crate) fn environment_path(path: &std::path::Path) -> std::path::PathBuf {
+    path.with_extension("meta.json")
+}
+
+/// Save a record of the current environment next to the artifact at `path`.
+pub(crate) fn write(path: &std::path::Path) -> anyhow::Result<()> {
+    let environment = Environment {
+        os: std::env::consts::OS,
+        arch: std::env::consts::ARCH,
+        twenty_twenty: env!("CARGO_PKG_VERSION"),
+        ffmpeg: ffmpeg_version(),
+    };
+    let environment_path = environment_path(path);
+    std::fs::write(&environment_path, serde_json::to_vec_pretty(&environment)?)
+        .map_err(|e| anyhow::anyhow!("unable to write {}: {}", environment_path.display(), e))
+}
+
+/// The version of libavcodec, as `major.minor.micro`.
+#[cfg(feature = "h264")]
+fn ffmpeg_version() -> Option<String> {
+    let version = ffmpeg_next::codec::version();
+    Some(format!(
+        "{}.{}.{}",
+        version >> 16,
+        (version >> 8) & 0xff,
+        version & 0xff
+    ))
+}
+
+#[cfg(not(feature = "h264"))]
+fn ffmpeg_version() -> Option<String> {
+    None
+}
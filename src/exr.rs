@@ -0,0 +1,159 @@
+//! Comparing high dynamic range, floating point images, like the OpenEXR output of offline
+//! renderers, without throwing away the range above 1.0 by converting to 8 bits.
+
+/// Compare the contents of the OpenEXR file to the floating point image provided.
+/// The color channels are normalized by the brightest value in either image, so the comparison
+/// runs over the full dynamic range, and compared with SSIM. Alpha is ignored.
+/// If the two are less similar than the `min_permissible_similarity` threshold,
+/// the test will fail.
+/// The `min_permissible_similarity` is a float between 0 and 1.
+/// If the images are the exact same, the score will be 1.
+/// To tonemap the images before comparing them instead, see [`crate::Config::tonemap`].
+#[track_caller]
+pub fn assert_exr<P: AsRef<std::path::Path>>(path: P, actual: &image::Rgba32FImage, min_permissible_similarity: f64) {
+    crate::Config::default().assert_exr(path, actual, min_permissible_similarity)
+}
+
+pub(crate) fn assert_exr_impl(
+    path: &std::path::Path,
+    actual: &image::Rgba32FImage,
+    min_permissible_similarity: f64,
+    config: &crate::Config,
+) -> anyhow::Result<()> {
+    if config.skipped() {
+        return Ok(());
+    }
+
+    let path = crate::resolve_reference_path(path);
+    let result = compare_exr(&path, actual, min_permissible_similarity, config);
+    crate::report::record(&path, &result);
+    result
+}
+
+fn compare_exr(
+    path: &std::path::Path,
+    actual: &image::Rgba32FImage,
+    min_permissible_similarity: f64,
+    config: &crate::Config,
+) -> anyhow::Result<()> {
+    let mode = crate::Mode::from_env();
+    let overwrite = || -> anyhow::Result<()> {
+        save_exr(actual, path)?;
+        crate::checksum::write(path)?;
+        crate::check_reference_size(path, config)
+    };
+    // Leave the image pending review next to the reference, along with its score, and say where
+    // it went.
+    let save_pending = |score: Option<f64>| -> anyhow::Result<String> {
+        let pending = crate::pending_path(path);
+        save_exr(actual, &pending)?;
+        crate::write_pending_score(&pending, path, score, min_permissible_similarity)?;
+        Ok(format!(
+            "\n                saved pending reference to {}",
+            pending.display()
+        ))
+    };
+
+    if mode == crate::Mode::Overwrite {
+        return overwrite();
+    }
+
+    crate::checksum::verify(path)?;
+    let expected = match image::open(path) {
+        Ok(image) => image.to_rgba32f(),
+        Err(image::ImageError::IoError(e)) if e.kind() == std::io::ErrorKind::NotFound => {
+            if mode.requires_reference(config) {
+                anyhow::bail!(
+                    "image (`{}`) does not exist, set {}=overwrite to create it",
+                    path.display(),
+                    crate::CRATE_ENV_VAR
+                );
+            }
+            // We take the dimensions from the original image.
+            image::Rgba32FImage::new(actual.width(), actual.height())
+        }
+        Err(e) => return Err(crate::reference_decode_error(path, e)),
+    };
+
+    let score = match ssim(&expected, actual, config.tonemap) {
+        // Images that can't be compared at all, e.g. because their sizes differ, don't match.
+        Err(_) if mode == crate::Mode::OverwriteOnMismatch => return overwrite(),
+        Err(e) if mode == crate::Mode::Pending => anyhow::bail!("{}{}", e, save_pending(None)?),
+        result => result?,
+    };
+    let image_mismatch = score < min_permissible_similarity;
+    crate::report::notify(path, score, min_permissible_similarity, !image_mismatch, mode);
+
+    if mode == crate::Mode::OverwriteOnMismatch {
+        return if image_mismatch { overwrite() } else { Ok(()) };
+    }
+
+    let mut saved = String::new();
+    if mode == crate::Mode::StoreArtifact || (mode == crate::Mode::StoreArtifactOnMismatch && image_mismatch) {
+        let artifact = crate::labelled_artifact_path(path, config.label.as_deref());
+        save_exr(actual, &artifact)?;
+        crate::environment::write(&artifact)?;
+        saved.push_str(&format!("\n                saved actual to {}", artifact.display()));
+    }
+
+    if mode == crate::Mode::Pending {
+        if image_mismatch {
+            saved.push_str(&save_pending(Some(score))?);
+        } else {
+            crate::remove_pending(path)?;
+        }
+    }
+
+    if image_mismatch {
+        anyhow::bail!(
+            r#"image (`{}`) {}{}
+                set {}=overwrite if these changes are intentional"#,
+            path.display(),
+            crate::Metric::Ssim.describe_mismatch(score, min_permissible_similarity),
+            saved,
+            crate::CRATE_ENV_VAR
+        )
+    }
+
+    Ok(())
+}
+
+/// Save the image as an OpenEXR file, creating any missing parent directories.
+fn save_exr(image: &image::Rgba32FImage, path: &std::path::Path) -> anyhow::Result<()> {
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    image::DynamicImage::ImageRgba32F(image.clone())
+        .save_with_format(path, image::ImageFormat::OpenExr)
+        .map_err(|e| anyhow::anyhow!("unable to write image to {}: {}", path.display(), e))
+}
+
+/// The mean SSIM of the color channels of the images, over non-overlapping windows.
+/// Values are normalized to `[0, 1]`, either by the brightest value in either image or, when
+/// `tonemap` is set, with the Reinhard operator.
+fn ssim(expected: &image::Rgba32FImage, actual: &image::Rgba32FImage, tonemap: bool) -> anyhow::Result<f64> {
+    crate::metric::ensure_same_size(expected.dimensions(), actual.dimensions())?;
+
+    let peak = expected
+        .pixels()
+        .chain(actual.pixels())
+        .flat_map(|pixel| pixel.0.into_iter().take(3))
+        .fold(0.0_f32, f32::max)
+        .max(f32::MIN_POSITIVE);
+    let normalize = |value: f32| {
+        let value = value.max(0.0) as f64;
+        if tonemap {
+            value / (1.0 + value)
+        } else {
+            value / peak as f64
+        }
+    };
+
+    let (width, height) = expected.dimensions();
+    Ok(crate::metric::windowed_ssim(
+        width,
+        height,
+        |x, y, channel| normalize(expected.get_pixel(x, y)[channel]),
+        |x, y, channel| normalize(actual.get_pixel(x, y)[channel]),
+    ))
+}
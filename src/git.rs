@@ -0,0 +1,31 @@
+//! Reading references as they were committed, rather than as they are in the working tree.
+
+/// The contents of the file at `path` as of `git HEAD`, or `None` if it isn't committed.
+pub(crate) fn show_head(path: &std::path::Path) -> anyhow::Result<Option<Vec<u8>>> {
+    // Run git from the file's own directory, so both relative and absolute paths resolve within
+    // whichever repository holds the file.
+    let dir = match path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => std::path::Path::new("."),
+    };
+    let Some(file_name) = path.file_name() else {
+        anyhow::bail!("`{}` is not a file", path.display());
+    };
+
+    let output = std::process::Command::new("git")
+        .arg("-C")
+        .arg(dir)
+        .arg("show")
+        .arg(format!("HEAD:./{}", file_name.to_string_lossy()))
+        .output()
+        .map_err(|e| anyhow::anyhow!("unable to run git: {e}"))?;
+    if output.status.success() {
+        return Ok(Some(output.stdout));
+    }
+
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    if stderr.contains("does not exist in") || stderr.contains("exists on disk, but not in") {
+        return Ok(None);
+    }
+    anyhow::bail!("unable to read `{}` from git HEAD: {}", path.display(), stderr.trim())
+}
@@ -1,8 +1,17 @@
-use std::io::Write;
-
 use anyhow::Result;
 use ffmpeg_next as ffmpeg;
 
+/// Which part of a decoded frame to compare, see [`crate::Config::plane`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Plane {
+    /// The frame converted to RGB.
+    #[default]
+    Rgb,
+    /// Only the luma (Y) plane of the frame, as decoded and before any conversion to RGB, as a
+    /// grayscale image, to compare against a grayscale reference regardless of chroma.
+    Luma,
+}
+
 /// Compare the contents of the file to the H.264 frame provided.
 /// If the two are less similar than the `min_permissible_similarity` threshold,
 /// the test will fail.
@@ -12,68 +21,826 @@ use ffmpeg_next as ffmpeg;
 /// in a UI like GitHub's.
 #[track_caller]
 pub fn assert_h264_frame<P: AsRef<std::path::Path>>(path: P, actual: &[u8], min_permissible_similarity: f64) {
-    match h264_frame_to_image(actual) {
-        Ok(image) => {
-            if let Err(e) = super::assert_image_impl(path, &image, min_permissible_similarity) {
-                panic!("assertion failed: {e}")
+    crate::Config::default().assert_h264_frame(path, actual, min_permissible_similarity)
+}
+
+/// Compare the contents of the file to the first frame of the raw H.264 stream read from
+/// `reader`, like [`assert_h264_frame`], for streams too large to hold in memory as a whole.
+/// Only as much of the stream as it takes to decode the frame is read.
+#[track_caller]
+pub fn assert_h264_frame_reader<P: AsRef<std::path::Path>, R: std::io::Read>(
+    path: P,
+    reader: R,
+    min_permissible_similarity: f64,
+) {
+    crate::Config::default().assert_h264_frame_reader(path, reader, min_permissible_similarity)
+}
+
+/// Compare frame `frame_index` of the video in the container file at `container_path`, like an
+/// `.mp4`, to the reference image at `reference_path`.
+/// If the two are less similar than the `min_permissible_similarity` threshold,
+/// the test will fail.
+/// The `min_permissible_similarity` is a float between 0 and 1.
+/// If the images are the exact same, the score will be 1.
+/// The container is demuxed by ffmpeg, so any format it supports works, including raw H.264.
+/// The video is decoded from the start rather than seeking to the frame, so the frame is the same
+/// one a player shows however far it is from the keyframe before it, at the cost of decoding
+/// every frame up to it.
+#[track_caller]
+pub fn assert_video_file_frame<P: AsRef<std::path::Path>, Q: AsRef<std::path::Path>>(
+    container_path: P,
+    reference_path: Q,
+    min_permissible_similarity: f64,
+    frame_index: usize,
+) {
+    crate::Config::default().assert_video_file_frame(
+        container_path,
+        reference_path,
+        min_permissible_similarity,
+        frame_index,
+    )
+}
+
+/// Compare every frame of the H.264 clip provided to the frames of the animated PNG (APNG) file.
+/// If the frame counts differ, or any frame is less similar than the
+/// `min_permissible_similarity` threshold, the test will fail.
+/// The `min_permissible_similarity` is a float between 0 and 1.
+/// If the frames are the exact same, their score will be 1.
+/// Overwrite mode encodes the decoded frames into an APNG.
+#[track_caller]
+pub fn assert_h264_animation<P: AsRef<std::path::Path>>(path: P, actual: &[u8], min_permissible_similarity: f64) {
+    crate::Config::default().assert_h264_animation(path, actual, min_permissible_similarity)
+}
+
+/// Compare each frame of the H.264 clip provided to its own reference image, holding frame `i` to
+/// `thresholds[i]`, e.g. to give inherently noisy frames like transitions looser thresholds.
+/// The reference for frame `i` is `path_pattern` with `{}` replaced by `i`, so `frame-{}.png`
+/// compares the first frame to `frame-0.png`.
+/// If there isn't exactly one threshold per frame, or any frame is less similar than its
+/// threshold, the test will fail.
+/// The thresholds are floats between 0 and 1.
+/// If the frames are the exact same, their score will be 1.
+#[track_caller]
+pub fn assert_h264_frames(path_pattern: &str, actual: &[u8], thresholds: &[f64]) {
+    crate::Config::default().assert_h264_frames(path_pattern, actual, thresholds)
+}
+
+pub(crate) fn assert_h264_frames_impl(
+    path_pattern: &str,
+    actual: &[u8],
+    thresholds: &[f64],
+    config: &crate::Config,
+) -> Result<()> {
+    if config.skipped() {
+        return Ok(());
+    }
+    anyhow::ensure!(
+        path_pattern.contains("{}"),
+        "the path pattern `{path_pattern}` has no `{{}}` to put the frame number in"
+    );
+
+    let frames = decode_packets(actual, false, config.skip_frames, config.plane)
+        .map_err(|e| anyhow::anyhow!("could not convert H.264 frames to images: {e}"))?;
+    if frames.len() != thresholds.len() {
+        anyhow::bail!(
+            "the H.264 clip has {} frames but {} thresholds were given",
+            frames.len(),
+            thresholds.len()
+        );
+    }
+
+    // Compare every frame before failing, so one run shows every frame that changed.
+    let mut failures = Vec::new();
+    for (index, (frame, &threshold)) in frames.iter().zip(thresholds).enumerate() {
+        let path = path_pattern.replace("{}", &index.to_string());
+        if let Err(e) = crate::assert_image_impl(path, frame, threshold, config) {
+            failures.push(e.to_string());
+        }
+    }
+    if !failures.is_empty() {
+        anyhow::bail!("{}", failures.join("\n"));
+    }
+
+    Ok(())
+}
+
+pub(crate) fn assert_h264_animation_impl(
+    path: &std::path::Path,
+    actual: &[u8],
+    min_permissible_similarity: f64,
+    config: &crate::Config,
+) -> Result<()> {
+    if config.skipped() {
+        return Ok(());
+    }
+
+    let path = crate::resolve_reference_path(path);
+    let result = decode_packets(actual, false, config.skip_frames, config.plane)
+        .map_err(|e| anyhow::anyhow!("could not convert H.264 frames to images: {e}"))
+        .and_then(|actual| compare_animation(&path, actual, min_permissible_similarity, config));
+    crate::report::record(&path, &result);
+    result
+}
+
+/// Compare only the keyframes of the H.264 clip provided to the frames of the animated PNG
+/// (APNG) file, which is much quicker than decoding every frame of a long clip while still
+/// catching gross regressions.
+/// The number of keyframes checked is included in the failure message.
+/// If the keyframe counts differ, or any keyframe is less similar than the
+/// `min_permissible_similarity` threshold, the test will fail.
+/// The `min_permissible_similarity` is a float between 0 and 1.
+/// If the keyframes are the exact same, their score will be 1.
+/// Overwrite mode encodes the decoded keyframes into an APNG.
+#[track_caller]
+pub fn assert_h264_keyframes<P: AsRef<std::path::Path>>(path: P, actual: &[u8], min_permissible_similarity: f64) {
+    crate::Config::default().assert_h264_keyframes(path, actual, min_permissible_similarity)
+}
+
+pub(crate) fn assert_h264_keyframes_impl(
+    path: &std::path::Path,
+    actual: &[u8],
+    min_permissible_similarity: f64,
+    config: &crate::Config,
+) -> Result<()> {
+    if config.skipped() {
+        return Ok(());
+    }
+
+    let path = crate::resolve_reference_path(path);
+    let result = decode_packets(actual, true, config.skip_frames, config.plane)
+        .map_err(|e| anyhow::anyhow!("could not convert H.264 keyframes to images: {e}"))
+        .and_then(|actual| {
+            let keyframes = actual.len();
+            compare_animation(&path, actual, min_permissible_similarity, config)
+                .map_err(|e| anyhow::anyhow!("{e}\n                checked {keyframes} keyframes"))
+        });
+    crate::report::record(&path, &result);
+    result
+}
+
+fn compare_animation(
+    path: &std::path::Path,
+    actual: Vec<image::DynamicImage>,
+    min_permissible_similarity: f64,
+    config: &crate::Config,
+) -> Result<()> {
+    let mode = crate::Mode::from_env();
+
+    let overwrite = || -> Result<()> {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        write_apng(path, &actual)?;
+        crate::checksum::write(path)?;
+        crate::check_reference_size(path, config)
+    };
+    // Leave the clip pending review next to the reference, along with the lowest score of its
+    // frames, and say where it went.
+    let save_pending = |score: Option<f64>| -> Result<String> {
+        let pending = crate::pending_path(path);
+        if let Some(parent) = pending.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        write_apng(&pending, &actual)?;
+        crate::write_pending_score(&pending, path, score, min_permissible_similarity)?;
+        Ok(format!(
+            "\n                saved pending reference to {}",
+            pending.display()
+        ))
+    };
+
+    if mode == crate::Mode::Overwrite {
+        return overwrite();
+    }
+
+    crate::checksum::verify(path)?;
+    let expected = match std::fs::File::open(path) {
+        Ok(file) => read_apng(file).map_err(|e| crate::reference_decode_error(path, e))?,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound && !mode.requires_reference(config) => {
+            // Treat a nonexistent file like an empty image for each frame.
+            actual
+                .iter()
+                .map(|frame| image::DynamicImage::new(frame.width(), frame.height(), frame.color()))
+                .collect()
+        }
+        Err(e) => anyhow::bail!("unable to read contents of {}: {}", path.display(), e),
+    };
+    if expected.len() != actual.len() {
+        if mode == crate::Mode::OverwriteOnMismatch {
+            return overwrite();
+        }
+        let saved = if mode == crate::Mode::Pending {
+            save_pending(None)?
+        } else {
+            String::new()
+        };
+        anyhow::bail!(
+            "animation (`{}`) has {} frames but the H.264 clip has {}{}",
+            path.display(),
+            expected.len(),
+            actual.len(),
+            saved
+        );
+    }
+
+    let mut mismatches = Vec::new();
+    let mut lowest_score = f64::INFINITY;
+    for (index, (expected, actual)) in expected.iter().zip(&actual).enumerate() {
+        let score = match crate::score(expected, actual, min_permissible_similarity, config) {
+            // Frames that can't be compared at all, e.g. because their sizes differ, don't match.
+            Err(_) if mode == crate::Mode::OverwriteOnMismatch => return overwrite(),
+            Err(e) if mode == crate::Mode::Pending => anyhow::bail!("{}{}", e, save_pending(None)?),
+            result => result?,
+        };
+        lowest_score = lowest_score.min(score);
+        crate::report::notify(
+            path,
+            score,
+            min_permissible_similarity,
+            score >= min_permissible_similarity,
+            mode,
+        );
+        if score < min_permissible_similarity {
+            mismatches.push(format!(
+                "frame {} {}",
+                index,
+                config.metric.describe_mismatch(score, min_permissible_similarity)
+            ));
+        }
+    }
+
+    if mode == crate::Mode::OverwriteOnMismatch {
+        return if mismatches.is_empty() { Ok(()) } else { overwrite() };
+    }
+
+    let mut saved = String::new();
+    if mode == crate::Mode::StoreArtifact || (mode == crate::Mode::StoreArtifactOnMismatch && !mismatches.is_empty()) {
+        let artifact_path = crate::labelled_artifact_path(path, config.label.as_deref());
+        if let Some(parent) = artifact_path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        write_apng(&artifact_path, &actual)?;
+        crate::environment::write(&artifact_path)?;
+        saved.push_str(&format!(
+            "\n                saved actual to {}",
+            artifact_path.display()
+        ));
+    }
+
+    if mode == crate::Mode::Pending {
+        if mismatches.is_empty() {
+            crate::remove_pending(path)?;
+        } else {
+            saved.push_str(&save_pending(Some(lowest_score))?);
+        }
+    }
+
+    if !mismatches.is_empty() {
+        anyhow::bail!(
+            r#"animation (`{}`) {}{}
+                set {}=overwrite if these changes are intentional"#,
+            path.display(),
+            mismatches.join(", "),
+            saved,
+            crate::CRATE_ENV_VAR
+        )
+    }
+
+    Ok(())
+}
+
+/// Decode every frame of an animated PNG.
+fn read_apng(file: std::fs::File) -> Result<Vec<image::DynamicImage>> {
+    use image::AnimationDecoder;
+
+    let decoder = image::codecs::png::PngDecoder::new(std::io::BufReader::new(file))?;
+    let frames = decoder.apng()?.into_frames().collect_frames()?;
+    Ok(frames
+        .into_iter()
+        .map(|frame| image::DynamicImage::ImageRgba8(frame.into_buffer()))
+        .collect())
+}
+
+/// Encode the frames, which must all be the same size, into an animated PNG.
+fn write_apng(path: &std::path::Path, frames: &[image::DynamicImage]) -> Result<()> {
+    let Some(first) = frames.first() else {
+        anyhow::bail!("cannot write an animation with no frames to {}", path.display());
+    };
+
+    let file = std::io::BufWriter::new(std::fs::File::create(path)?);
+    let mut encoder = png::Encoder::new(file, first.width(), first.height());
+    encoder.set_color(png::ColorType::Rgba);
+    encoder.set_depth(png::BitDepth::Eight);
+    encoder.set_animated(frames.len() as u32, 0)?;
+    let mut writer = encoder.write_header()?;
+    for frame in frames {
+        writer.write_image_data(frame.to_rgba8().as_raw())?;
+    }
+    writer.finish()?;
+
+    Ok(())
+}
+
+/// How similar two H.264 clips are across all their frames.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct VideoComparison {
+    /// The mean SSIM score of the frames.
+    pub mean: f64,
+    /// The SSIM score of the least similar frame.
+    pub worst: f64,
+    /// The index of the least similar frame.
+    pub worst_frame: usize,
+}
+
+/// Compare two H.264 clips frame by frame, e.g. read from files with [`std::fs::read`], and
+/// summarize how similar they are across the whole clip.
+/// Fails if the clips have different numbers of frames, or no frames at all.
+/// The `TWENTY_TWENTY` modes have no effect.
+pub fn compare_videos(expected: &[u8], actual: &[u8]) -> Result<VideoComparison> {
+    let expected = decode_h264_frames(expected)
+        .map_err(|e| anyhow::anyhow!("could not convert the expected H.264 frames to images: {e}"))?;
+    let actual = decode_h264_frames(actual)
+        .map_err(|e| anyhow::anyhow!("could not convert the actual H.264 frames to images: {e}"))?;
+    if expected.len() != actual.len() {
+        anyhow::bail!(
+            "the expected H.264 clip has {} frames but the actual clip has {}",
+            expected.len(),
+            actual.len()
+        );
+    }
+    anyhow::ensure!(!actual.is_empty(), "the H.264 clips have no frames to compare");
+
+    let scores = expected
+        .iter()
+        .zip(&actual)
+        .map(|(expected, actual)| crate::compare_images(expected, actual))
+        .collect::<Result<Vec<_>>>()?;
+    let (worst_frame, worst) = scores
+        .iter()
+        .copied()
+        .enumerate()
+        .min_by(|(_, a), (_, b)| a.total_cmp(b))
+        .unwrap_or_default();
+    Ok(VideoComparison {
+        mean: scores.iter().sum::<f64>() / scores.len() as f64,
+        worst,
+        worst_frame,
+    })
+}
+
+/// Decode the first frame of the H.264 data to an image, the same way [`assert_h264_frame`] does,
+/// e.g. to make your own comparison or save a new reference.
+pub fn decode_h264_frame(data: &[u8]) -> Result<image::DynamicImage> {
+    decode_first_frame(data, 0, Plane::Rgb)
+}
+
+/// Decode the first frame of the H.264 data after the first `skip_frames` to an image of the
+/// plane.
+pub(crate) fn decode_first_frame(data: &[u8], skip_frames: usize, plane: Plane) -> Result<image::DynamicImage> {
+    // Initialize the FFmpeg library
+    ffmpeg::init()?;
+
+    let temp_file = write_temp_file(data)?;
+
+    // Create a decoder for the H.264 format
+    let ictx = ffmpeg::format::input(temp_file.path()).map_err(|e| anyhow::anyhow!(e))?;
+    let images = decode_through(ictx, || no_video_stream(data), skip_frames, plane)?;
+    images.into_iter().nth(skip_frames).ok_or_else(|| drained(skip_frames))
+}
+
+/// Decode the first frame of the H.264 stream read from `reader`, like [`decode_h264_frame`],
+/// without holding the whole stream in memory.
+/// The stream must be raw H.264, not in a container. It is read in chunks that are fed to the
+/// decoder as they arrive, and reading stops as soon as the first frame is decoded.
+pub fn decode_h264_frame_reader<R: std::io::Read>(reader: R) -> Result<image::DynamicImage> {
+    decode_first_frame_reader(reader, 0, Plane::Rgb)
+}
+
+/// How much of a stream is read at a time when decoding it from a reader.
+const READ_CHUNK_SIZE: usize = 64 * 1024;
+
+/// Decode the first frame of the H.264 stream read from `reader` after the first `skip_frames`
+/// to an image of the plane.
+pub(crate) fn decode_first_frame_reader<R: std::io::Read>(
+    mut reader: R,
+    skip_frames: usize,
+    plane: Plane,
+) -> Result<image::DynamicImage> {
+    // Initialize the FFmpeg library
+    ffmpeg::init()?;
+
+    let codec = ffmpeg::codec::decoder::find(ffmpeg::codec::Id::H264)
+        .ok_or_else(|| anyhow::anyhow!("no H.264 decoder is available"))?;
+    let mut video_decoder = ffmpeg::codec::context::Context::new_with_codec(codec)
+        .decoder()
+        .video()?;
+    let mut parser = Parser::h264()?;
+
+    // The parser may read a little past the end of the data it is given, so what was read is
+    // always followed by zeros.
+    let padding = ffmpeg::ffi::AV_INPUT_BUFFER_PADDING_SIZE as usize;
+    let mut chunk = vec![0; READ_CHUNK_SIZE + padding];
+    let mut images = Vec::new();
+    loop {
+        let read = match reader.read(&mut chunk[..READ_CHUNK_SIZE]) {
+            Ok(read) => read,
+            Err(e) if e.kind() == std::io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e.into()),
+        };
+        chunk[read..read + padding].fill(0);
+
+        // Split what was read into packets, or at the end of the stream, flush the parser of the
+        // packet it is holding on to.
+        let mut data = &chunk[..read];
+        loop {
+            let (consumed, packet) = parser.parse(&mut video_decoder, data)?;
+            data = &data[consumed..];
+            let Some(packet) = packet else {
+                if data.is_empty() {
+                    break;
+                }
+                continue;
+            };
+            video_decoder.send_packet(&packet)?;
+            receive_frames(&mut video_decoder, &mut images, plane)?;
+            if images.len() > skip_frames {
+                return Ok(images.swap_remove(skip_frames));
+            }
+            if data.is_empty() && read > 0 {
+                break;
             }
         }
-        Err(e) => {
-            panic!("could not convert H.264 frame to image: {e}")
+
+        if read == 0 {
+            break;
         }
     }
+    // Some encoders produce streams where the decoder holds on to frames until it knows no more
+    // input is coming, so signal the end of the stream and drain it.
+    video_decoder.send_eof()?;
+    receive_frames(&mut video_decoder, &mut images, plane)?;
+
+    images.into_iter().nth(skip_frames).ok_or_else(|| drained(skip_frames))
 }
 
-// Convert a H264 frame to an image.
-pub(crate) fn h264_frame_to_image(data: &[u8]) -> Result<image::DynamicImage> {
+/// An FFmpeg parser that splits a raw H.264 stream into the packets the decoder takes, closed when
+/// it is dropped.
+struct Parser(*mut ffmpeg::ffi::AVCodecParserContext);
+
+impl Parser {
+    fn h264() -> Result<Self> {
+        // SAFETY: the parser returned, if any, is owned by us until it is closed on drop.
+        let parser = unsafe { ffmpeg::ffi::av_parser_init(ffmpeg::ffi::AVCodecID::AV_CODEC_ID_H264 as i32) };
+        if parser.is_null() {
+            anyhow::bail!("no H.264 parser is available");
+        }
+        Ok(Self(parser))
+    }
+
+    /// Feed the parser `data`, returning how many bytes of it were consumed and the packet that
+    /// completed, if any. Empty `data` marks the end of the stream, flushing the last packet.
+    fn parse(
+        &mut self,
+        video_decoder: &mut ffmpeg::codec::decoder::Video,
+        data: &[u8],
+    ) -> Result<(usize, Option<ffmpeg::Packet>)> {
+        let mut out = std::ptr::null_mut();
+        let mut out_size = 0;
+        // SAFETY: the parser and the decoder's context are valid for the call, and `data` is
+        // followed by zeroed padding as the parser requires.
+        let consumed = unsafe {
+            ffmpeg::ffi::av_parser_parse2(
+                self.0,
+                video_decoder.as_mut_ptr(),
+                &mut out,
+                &mut out_size,
+                data.as_ptr(),
+                data.len() as i32,
+                ffmpeg::ffi::AV_NOPTS_VALUE,
+                ffmpeg::ffi::AV_NOPTS_VALUE,
+                0,
+            )
+        };
+        if consumed < 0 {
+            return Err(ffmpeg::Error::from(consumed).into());
+        }
+        if out_size <= 0 {
+            return Ok((consumed as usize, None));
+        }
+        // SAFETY: the packet points into the parser's buffer or `data`, and is copied out before
+        // either is used again.
+        let packet = unsafe { std::slice::from_raw_parts(out, out_size as usize) };
+        Ok((consumed as usize, Some(ffmpeg::Packet::copy(packet))))
+    }
+}
+
+impl Drop for Parser {
+    fn drop(&mut self) {
+        // SAFETY: the parser was opened by `Parser::h264` and is not used after this.
+        unsafe { ffmpeg::ffi::av_parser_close(self.0) }
+    }
+}
+
+/// Decode the best video stream of the input from the start until frame `frame_index`, feeding
+/// the decoder packet by packet as the demuxer splits the stream up, so a frame spread over
+/// several packets, like the NAL units of raw H.264, is decoded whole. If the stream ends first,
+/// the decoder is drained of the frames it held on to.
+/// Returns every frame decoded, which is no more than `frame_index` if the stream is too short.
+fn decode_through(
+    mut ictx: ffmpeg::format::context::Input,
+    no_video_stream: impl FnOnce() -> anyhow::Error,
+    frame_index: usize,
+    plane: Plane,
+) -> Result<Vec<image::DynamicImage>> {
+    let (stream_index, mut video_decoder) = {
+        let input = ictx
+            .streams()
+            .best(ffmpeg::media::Type::Video)
+            .ok_or_else(no_video_stream)?;
+        let context = ffmpeg::codec::context::Context::from_parameters(input.parameters())?;
+        (input.index(), context.decoder().video()?)
+    };
+
+    let mut images = Vec::new();
+    for (stream, packet) in ictx.packets() {
+        if stream.index() == stream_index {
+            video_decoder.send_packet(&packet)?;
+            receive_frames(&mut video_decoder, &mut images, plane)?;
+            if images.len() > frame_index {
+                return Ok(images);
+            }
+        }
+    }
+    // Some encoders produce streams where the decoder holds on to frames until it knows no more
+    // input is coming, so signal the end of the stream and drain it.
+    video_decoder.send_eof()?;
+    receive_frames(&mut video_decoder, &mut images, plane)?;
+
+    Ok(images)
+}
+
+/// The error for a stream the decoder was drained of without producing a frame past the first
+/// `skip_frames`.
+fn drained(skip_frames: usize) -> anyhow::Error {
+    if skip_frames == 0 {
+        anyhow::anyhow!("the H.264 decoder was drained without producing a frame")
+    } else {
+        anyhow::anyhow!("the H.264 decoder was drained without producing a frame after skipping {skip_frames}")
+    }
+}
+
+/// Decode frame `frame_index` of the video in the container file at `path`, like an `.mp4`, the
+/// same way [`assert_video_file_frame`] does.
+/// The video is decoded from the start, as the frames before it may be needed to decode it.
+pub fn decode_video_file_frame<P: AsRef<std::path::Path>>(path: P, frame_index: usize) -> Result<image::DynamicImage> {
+    decode_file_frame(path.as_ref(), frame_index, Plane::Rgb)
+}
+
+/// Decode frame `frame_index` of the video in the container file at `path` to an image of the
+/// plane.
+pub(crate) fn decode_file_frame(
+    path: &std::path::Path,
+    frame_index: usize,
+    plane: Plane,
+) -> Result<image::DynamicImage> {
     // Initialize the FFmpeg library
     ffmpeg::init()?;
 
-    // Save the frame to a temporary file, we can read back out of.
-    // This will automatically be deleted when the program exits.
-    // TODO: this sucks we have to write this back out to disk, we should find a better way
-    // to create a decoder from just bytes.
-    let temp_file_name = std::env::temp_dir().join(format!("{}.h264", uuid::Uuid::new_v4()));
-    let mut temp_file = std::fs::File::create(&temp_file_name)?;
-    temp_file.write_all(data)?;
+    let ictx =
+        ffmpeg::format::input(&path).map_err(|e| anyhow::anyhow!("unable to open video {}: {}", path.display(), e))?;
+    let no_video_stream = || anyhow::anyhow!("no video stream found in {}", path.display());
+    let images = decode_through(ictx, no_video_stream, frame_index, plane)?;
+
+    let frames = images.len();
+    images.into_iter().nth(frame_index).ok_or_else(|| {
+        anyhow::anyhow!(
+            "video {} has {} frames, so it has no frame {}",
+            path.display(),
+            frames,
+            frame_index
+        )
+    })
+}
+
+/// Decode the first frame of the H.264 data, like [`decode_h264_frame`], and save it as a PNG at
+/// `out`, e.g. to generate a reference from a capture outside of a test.
+pub fn h264_frame_to_png<P: AsRef<std::path::Path>>(data: &[u8], out: P) -> Result<()> {
+    save_png(&decode_h264_frame(data)?, out.as_ref())
+}
+
+/// Decode every frame of the H.264 data, like [`decode_h264_frames`], and save them as PNGs in
+/// `dir`, named `frame-0.png`, `frame-1.png`, and so on, which suits [`assert_h264_frames`] with
+/// a pattern of `frame-{}.png`. Returns the number of frames saved.
+pub fn h264_frames_to_pngs<P: AsRef<std::path::Path>>(data: &[u8], dir: P) -> Result<usize> {
+    let frames = decode_h264_frames(data)?;
+    for (index, frame) in frames.iter().enumerate() {
+        save_png(frame, &dir.as_ref().join(format!("frame-{index}.png")))?;
+    }
+    Ok(frames.len())
+}
+
+/// Save the image as a PNG at `path` whatever its extension, creating its directory if needed.
+fn save_png(image: &image::DynamicImage, path: &std::path::Path) -> Result<()> {
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    image
+        .save_with_format(path, image::ImageFormat::Png)
+        .map_err(|e| anyhow::anyhow!("unable to write image to {}: {}", path.display(), e))
+}
+
+/// Decode every frame of the H.264 data to an image, the same way [`assert_h264_animation`]
+/// does.
+pub fn decode_h264_frames(data: &[u8]) -> Result<Vec<image::DynamicImage>> {
+    decode_packets(data, false, 0, Plane::Rgb)
+}
+
+/// Decode only the keyframes of the H.264 data to images, the same way
+/// [`assert_h264_keyframes`] does.
+pub fn decode_h264_keyframes(data: &[u8]) -> Result<Vec<image::DynamicImage>> {
+    decode_packets(data, true, 0, Plane::Rgb)
+}
+
+/// Decode the packets of the video stream, or just its keyframes, to images of the plane,
+/// discarding the first `skip_frames` frames of the stream, whether they are keyframes or not.
+fn decode_packets(
+    data: &[u8],
+    keyframes_only: bool,
+    skip_frames: usize,
+    plane: Plane,
+) -> Result<Vec<image::DynamicImage>> {
+    // Initialize the FFmpeg library
+    ffmpeg::init()?;
+
+    let temp_file = write_temp_file(data)?;
 
     // Create a decoder for the H.264 format
-    let ictx = ffmpeg::format::input(&temp_file_name).map_err(|e| anyhow::anyhow!(e))?;
-    let input = ictx
-        .streams()
-        .best(ffmpeg::media::Type::Video)
-        .ok_or(ffmpeg::Error::StreamNotFound)?;
-    let context = ffmpeg::codec::context::Context::from_parameters(input.parameters())?;
-    let mut video_decoder = context.decoder().video()?;
-
-    // Read the H.264 frame
+    let mut ictx = ffmpeg::format::input(temp_file.path()).map_err(|e| anyhow::anyhow!(e))?;
+    let (stream_index, mut video_decoder) = {
+        let input = ictx
+            .streams()
+            .best(ffmpeg::media::Type::Video)
+            .ok_or_else(|| no_video_stream(data))?;
+        let context = ffmpeg::codec::context::Context::from_parameters(input.parameters())?;
+        (input.index(), context.decoder().video()?)
+    };
+
+    // Feed the decoder the clip packet by packet, as the demuxer splits it up, then drain it.
+    // Keyframes don't depend on any other frame, so the rest, and the keyframes among the frames
+    // to skip, can be skipped without decoding them.
+    let mut images = Vec::new();
+    let mut frame_index = 0;
+    for (stream, packet) in ictx.packets() {
+        if stream.index() != stream_index {
+            continue;
+        }
+        let wanted = !keyframes_only || (packet.is_key() && frame_index >= skip_frames);
+        frame_index += 1;
+        if wanted {
+            video_decoder.send_packet(&packet)?;
+            receive_frames(&mut video_decoder, &mut images, plane)?;
+        }
+    }
+    video_decoder.send_eof()?;
+    receive_frames(&mut video_decoder, &mut images, plane)?;
+
+    // Every other frame has to be decoded, as the frames after the skipped ones refer to them.
+    if keyframes_only {
+        return Ok(images);
+    }
+    Ok(images.split_off(skip_frames.min(images.len())))
+}
+
+/// Receive every frame the decoder has ready, as images of the plane.
+fn receive_frames(
+    video_decoder: &mut ffmpeg::codec::decoder::Video,
+    images: &mut Vec<image::DynamicImage>,
+    plane: Plane,
+) -> Result<()> {
     let mut video_frame = ffmpeg::frame::Video::empty();
-    let packet = ffmpeg::packet::Packet::copy(data);
+    loop {
+        match video_decoder.receive_frame(&mut video_frame) {
+            Ok(()) => images.push(frame_to_image(&video_frame, plane)?),
+            Err(ffmpeg::Error::Other { errno }) if errno == ffmpeg::error::EAGAIN => return Ok(()),
+            Err(ffmpeg::Error::Eof) => return Ok(()),
+            Err(e) => return Err(e.into()),
+        }
+    }
+}
+
+/// The error for data with no video stream, which is usually audio or something that isn't
+/// H.264 at all.
+fn no_video_stream(data: &[u8]) -> anyhow::Error {
+    anyhow::anyhow!(
+        "no video stream found in the provided H.264 data of {} bytes (is this valid H.264?)",
+        data.len()
+    )
+}
+
+/// A temporary file FFmpeg reads a stream back out of, deleted when it is dropped.
+struct TempFile(std::path::PathBuf);
+
+impl TempFile {
+    fn path(&self) -> &std::path::Path {
+        &self.0
+    }
+}
 
-    // Decode the H.264 frame
-    video_decoder.send_packet(&packet)?;
-    video_decoder.receive_frame(&mut video_frame)?;
-    video_decoder.flush();
+impl Drop for TempFile {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.0);
+    }
+}
+
+/// Save the data to a temporary file, we can read back out of.
+fn write_temp_file(data: &[u8]) -> Result<TempFile> {
+    // TODO: this sucks we have to write this back out to disk, we should find a better way
+    // to create a decoder from just bytes.
+    let temp_file = TempFile(std::env::temp_dir().join(format!("{}.h264", uuid::Uuid::new_v4())));
+    std::fs::write(temp_file.path(), data)?;
+    Ok(temp_file)
+}
+
+/// Convert a decoded frame to an image of the plane.
+fn frame_to_image(video_frame: &ffmpeg::frame::Video, plane: Plane) -> Result<image::DynamicImage> {
+    if plane == Plane::Luma {
+        return frame_to_luma(video_frame);
+    }
 
     // Get the pixel format of the decoded frame
-    let pixel_format = video_frame.format();
-    if pixel_format != ffmpeg::format::Pixel::RGB24 {
-        let mut converted_video = ffmpeg::frame::Video::empty();
+    let mut converted_video = ffmpeg::frame::Video::empty();
+    let video_frame = if video_frame.format() != ffmpeg::format::Pixel::RGB24 {
         // Convert the decoded frame to an RGB format.
         video_frame
             .converter(ffmpeg::format::Pixel::RGB24)?
-            .run(&video_frame, &mut converted_video)?;
-        video_frame = converted_video;
+            .run(video_frame, &mut converted_video)?;
+        &converted_video
+    } else {
+        video_frame
+    };
+
+    // Create an image from the RGB frame.
+    // Rows are often padded out past `width * 3` bytes for alignment, so copy them one by one
+    // to strip the padding.
+    let row_len = video_frame.width() as usize * 3;
+    let stride = video_frame.stride(0);
+    let data = video_frame.data(0);
+    let mut pixels = Vec::with_capacity(row_len * video_frame.height() as usize);
+    for row in 0..video_frame.height() as usize {
+        let start = row * stride;
+        let Some(row) = data.get(start..start + row_len) else {
+            anyhow::bail!("the decoded frame is smaller than its dimensions");
+        };
+        pixels.extend_from_slice(row);
     }
+    let Some(raw) = image::RgbImage::from_raw(video_frame.width(), video_frame.height(), pixels) else {
+        anyhow::bail!("the container was not big enough as per: https://docs.rs/image/latest/image/struct.ImageBuffer.html#method.from_raw");
+    };
+
+    Ok(image::DynamicImage::ImageRgb8(raw))
+}
+
+/// Take the luma plane of a decoded frame as a grayscale image. The Y plane of the 8-bit YUV
+/// formats H.264 decodes to is used directly, and anything else is converted to grayscale.
+/// Limited range luma, from 16 to 235, is expanded to the full range of the grayscale image, so
+/// it lines up with the luma of a reference image.
+fn frame_to_luma(video_frame: &ffmpeg::frame::Video) -> Result<image::DynamicImage> {
+    use ffmpeg::format::Pixel;
 
-    // Convert the decoded frame to an RGB format
-    video_frame.set_format(ffmpeg::format::Pixel::RGB24);
+    let mut converted_video = ffmpeg::frame::Video::empty();
+    let (video_frame, full_range) = match video_frame.format() {
+        Pixel::YUVJ420P | Pixel::YUVJ422P | Pixel::YUVJ444P | Pixel::GRAY8 => (video_frame, true),
+        Pixel::YUV420P | Pixel::YUV422P | Pixel::YUV444P | Pixel::NV12 | Pixel::NV21 => {
+            (video_frame, video_frame.color_range() == ffmpeg::color::Range::JPEG)
+        }
+        _ => {
+            video_frame
+                .converter(Pixel::GRAY8)?
+                .run(video_frame, &mut converted_video)?;
+            (&converted_video, true)
+        }
+    };
 
-    // Create an image from the RGB frame
-    let Some(raw) = image::RgbImage::from_raw(video_frame.width(), video_frame.height(), video_frame.data(0).to_vec())
-    else {
+    // Like the RGB data, the rows of the plane may be padded past its width.
+    let row_len = video_frame.width() as usize;
+    let stride = video_frame.stride(0);
+    let data = video_frame.data(0);
+    let mut pixels = Vec::with_capacity(row_len * video_frame.height() as usize);
+    for row in 0..video_frame.height() as usize {
+        let start = row * stride;
+        let Some(row) = data.get(start..start + row_len) else {
+            anyhow::bail!("the decoded frame is smaller than its dimensions");
+        };
+        pixels.extend_from_slice(row);
+    }
+    if !full_range {
+        for luma in &mut pixels {
+            *luma = ((*luma as f32 - 16.0) * 255.0 / 219.0).round().clamp(0.0, 255.0) as u8;
+        }
+    }
+    let Some(raw) = image::GrayImage::from_raw(video_frame.width(), video_frame.height(), pixels) else {
         anyhow::bail!("the container was not big enough as per: https://docs.rs/image/latest/image/struct.ImageBuffer.html#method.from_raw");
     };
 
-    Ok(image::DynamicImage::ImageRgb8(raw))
+    Ok(image::DynamicImage::ImageLuma8(raw))
 }
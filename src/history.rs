@@ -0,0 +1,59 @@
+//! A sidecar file next to each reference holding the scores of its most recent comparisons, so a
+//! test trending towards its threshold can be caught before it fails.
+
+use serde::{Deserialize, Serialize};
+
+/// The environment variable holding how many scores to keep in each reference's history.
+pub(crate) const SCORE_HISTORY_ENV_VAR: &str = "TWENTY_TWENTY_SCORE_HISTORY";
+
+/// A single comparison in a reference's history.
+#[derive(Debug, Deserialize, Serialize)]
+struct Entry {
+    /// When the comparison was made, in seconds since the Unix epoch.
+    timestamp: u64,
+    /// The score of the comparison, or `None` if it wasn't finite, like the PSNR of identical
+    /// images.
+    score: Option<f64>,
+}
+
+/// The path of the score history sidecar of the reference at `path`.
+pub(crate) fn history_path(path: &std::path::Path) -> std::path::PathBuf {
+    path.with_extension("scores.json")
+}
+
+/// Append the score to the history of the reference at `path`, keeping the number of most recent
+/// scores set by `TWENTY_TWENTY_SCORE_HISTORY`.
+/// Does nothing if `TWENTY_TWENTY_SCORE_HISTORY` is not set.
+pub(crate) fn record(path: &std::path::Path, score: f64) -> anyhow::Result<()> {
+    let Ok(var) = std::env::var(SCORE_HISTORY_ENV_VAR) else {
+        return Ok(());
+    };
+    let limit: usize = var
+        .parse()
+        .map_err(|e| anyhow::anyhow!("{SCORE_HISTORY_ENV_VAR} must be a number of scores to keep: {e}"))?;
+    if limit == 0 {
+        return Ok(());
+    }
+
+    let history_path = history_path(path);
+    let mut entries: Vec<Entry> = match std::fs::read(&history_path) {
+        Ok(contents) => serde_json::from_slice(&contents)
+            .map_err(|e| anyhow::anyhow!("unable to parse score history {}: {}", history_path.display(), e))?,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Vec::new(),
+        Err(e) => anyhow::bail!("unable to read score history {}: {}", history_path.display(), e),
+    };
+
+    entries.push(Entry {
+        timestamp: std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or_default(),
+        score: score.is_finite().then_some(score),
+    });
+    if entries.len() > limit {
+        entries.drain(..entries.len() - limit);
+    }
+
+    std::fs::write(&history_path, serde_json::to_string_pretty(&entries)?)
+        .map_err(|e| anyhow::anyhow!("unable to write score history {}: {}", history_path.display(), e))
+}
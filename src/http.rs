@@ -0,0 +1,143 @@
+//! Comparing against references fetched over HTTP.
+
+use std::{collections::BTreeMap, sync::Mutex};
+
+/// The references downloaded so far, by URL, so each is fetched once per run.
+static CACHE: Mutex<BTreeMap<String, Vec<u8>>> = Mutex::new(BTreeMap::new());
+
+/// Compare the reference image at the URL to the image provided.
+/// If the two are less similar than the `min_permissible_similarity` threshold,
+/// the test will fail.
+/// The `min_permissible_similarity` is a float between 0 and 1.
+/// If the images are the exact same, the score will be 1.
+/// A reference that doesn't exist (a 404) is treated like an empty image, and overwrite mode
+/// uploads the image provided as a PNG with a `PUT` to the URL, as does overwrite-on-mismatch
+/// mode when it doesn't match. There is no file next to the reference for pending mode to leave
+/// the image in, so it goes under `artifacts/` instead, at the [`crate::pending_path`] of its
+/// [`crate::artifact_path`], to upload once it has been reviewed.
+/// Each reference is downloaded once per run.
+/// This uses a blocking HTTP client, so it can't be called from within an async runtime.
+#[track_caller]
+pub fn assert_image_url(url: &str, actual: &image::DynamicImage, min_permissible_similarity: f64) {
+    if let Err(e) = assert_image_url_impl(url, actual, min_permissible_similarity) {
+        panic!("assertion failed: {e}")
+    }
+}
+
+fn assert_image_url_impl(
+    url: &str,
+    actual: &image::DynamicImage,
+    min_permissible_similarity: f64,
+) -> anyhow::Result<()> {
+    if crate::Config::default().skipped() {
+        return Ok(());
+    }
+
+    let config = crate::Config::default();
+    let mode = crate::Mode::from_env();
+
+    if mode == crate::Mode::Overwrite {
+        return upload(url, actual);
+    }
+
+    let expected = match fetch(url)? {
+        Some(bytes) => {
+            image::load_from_memory(&bytes).map_err(|e| anyhow::anyhow!("unable to decode image (`{url}`): {e}"))?
+        }
+        // We take the dimensions and color type from the original image.
+        None => image::DynamicImage::new(actual.width(), actual.height(), actual.color()),
+    };
+
+    let pending_reference = crate::artifact_path(url);
+    let save_pending = |score: Option<f64>| -> anyhow::Result<String> {
+        let pending = crate::pending_path(&pending_reference);
+        crate::save_image(actual, &pending)?;
+        crate::write_pending_score(&pending, std::path::Path::new(url), score, min_permissible_similarity)?;
+        Ok(format!(
+            "\n                saved pending reference to {}",
+            pending.display()
+        ))
+    };
+
+    let score = match crate::score(&expected, actual, min_permissible_similarity, &config) {
+        // Images that can't be compared at all, e.g. because their sizes differ, don't match.
+        Err(_) if mode == crate::Mode::OverwriteOnMismatch => return upload(url, actual),
+        Err(e) if mode == crate::Mode::Pending => anyhow::bail!("{}{}", e, save_pending(None)?),
+        result => result?,
+    };
+    let image_mismatch = score < min_permissible_similarity;
+    crate::report::notify(
+        std::path::Path::new(url),
+        score,
+        min_permissible_similarity,
+        !image_mismatch,
+        mode,
+    );
+
+    if mode == crate::Mode::OverwriteOnMismatch {
+        return if image_mismatch { upload(url, actual) } else { Ok(()) };
+    }
+
+    let mut saved = String::new();
+    if mode == crate::Mode::Pending {
+        if image_mismatch {
+            saved = save_pending(Some(score))?;
+        } else {
+            crate::remove_pending(&pending_reference)?;
+        }
+    }
+
+    if image_mismatch {
+        anyhow::bail!(
+            r#"image (`{}`) {}{}
+                set {}=overwrite if these changes are intentional"#,
+            url,
+            config.metric.describe_mismatch(score, min_permissible_similarity),
+            saved,
+            crate::CRATE_ENV_VAR
+        )
+    }
+
+    Ok(())
+}
+
+/// Upload the image as a PNG with a `PUT` to the URL, as the new reference.
+fn upload(url: &str, actual: &image::DynamicImage) -> anyhow::Result<()> {
+    let mut png = Vec::new();
+    actual.write_to(&mut std::io::Cursor::new(&mut png), image::ImageFormat::Png)?;
+    reqwest::blocking::Client::new()
+        .put(url)
+        .header(reqwest::header::CONTENT_TYPE, "image/png")
+        .body(png.clone())
+        .send()
+        .and_then(reqwest::blocking::Response::error_for_status)
+        .map_err(|e| anyhow::anyhow!("unable to upload image to {url}: {e}"))?;
+    CACHE
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .insert(url.to_string(), png);
+    Ok(())
+}
+
+/// Fetch the reference at the URL, or `None` if it doesn't exist.
+fn fetch(url: &str) -> anyhow::Result<Option<Vec<u8>>> {
+    if let Some(bytes) = CACHE.lock().unwrap_or_else(|e| e.into_inner()).get(url) {
+        return Ok(Some(bytes.clone()));
+    }
+
+    let response = reqwest::blocking::get(url).map_err(|e| anyhow::anyhow!("unable to fetch {url}: {e}"))?;
+    if response.status() == reqwest::StatusCode::NOT_FOUND {
+        return Ok(None);
+    }
+    let bytes = response
+        .error_for_status()
+        .and_then(reqwest::blocking::Response::bytes)
+        .map_err(|e| anyhow::anyhow!("unable to fetch {url}: {e}"))?
+        .to_vec();
+
+    CACHE
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .insert(url.to_string(), bytes.clone());
+    Ok(Some(bytes))
+}
@@ -10,6 +10,16 @@
 //! quality degradation that is caused by processing such as data compression or by losses in data
 //! transmission. More information can be found [here](https://en.wikipedia.org/wiki/Structural_similarity).
 //!
+//! Whatever their color types, both images are converted to 8-bit RGBA before they are compared.
+//! Gray images are spread across the color channels, and images without an alpha channel are
+//! given an opaque one. If the reference and the actual image have different color types, a
+//! warning is printed to stderr, as the conversion can make for surprising scores.
+//!
+//! Indexed (paletted) PNGs are expanded to their palette colors, with the transparency of each
+//! entry, and gray with alpha keeps its alpha, so neither scores any differently from the same
+//! image saved as RGBA. Only 16-bit images lose anything, as they are rounded to 8 bits, so two
+//! 16-bit images that differ by less than that compare as identical.
+//!
 //! You will need `ffmpeg` installed on your system to use this library. This library uses
 //! the [ffmpeg bindings](https://docs.rs/ffmpeg-next/latest/ffmpeg_next/) in rust to convert the H.264 frames to images.
 //!
@@ -35,44 +45,206 @@
 //! If the output doesn't match, the program will `panic!` and emit the
 //! difference in the score.
 //!
+//! To tweak how the images are compared, build a [`Config`] and use its assertion methods.
+//!
 //! To accept the changes from `get_h264_frame()` or `get_image()`, run with `TWENTY_TWENTY=overwrite`.
+//!
+//! To only rewrite the references that no longer match, leaving the rest byte for byte as they
+//! are, run with `TWENTY_TWENTY=overwrite-on-mismatch`.
+//!
+//! To review changes like `insta` snapshots, run with `TWENTY_TWENTY=pending`. Each image that
+//! doesn't match is saved next to its reference, `foo.png` as `foo.png.new`, along with its score
+//! in `foo.png.new.json`, and the reviewer below offers to accept it.
+//!
+//! To skip every comparison without removing the assertions, e.g. while regenerating references
+//! in bulk, run with `TWENTY_TWENTY=off`.
+//!
+//! To run only some of the comparisons, e.g. a quick check of the critical ones before
+//! committing, tag them with `Config::tag` and list the tags to run, separated by commas, in
+//! `TWENTY_TWENTY_ONLY`. Every other comparison, tagged or not, passes without being made.
+//!
+//! To keep references out of your test code's directories, set `TWENTY_TWENTY_SNAPSHOT_DIR` and
+//! every reference path will be resolved relative to it, e.g. with
+//! `TWENTY_TWENTY_SNAPSHOT_DIR=snapshots`, `assert_image("foo.png", ...)` uses `snapshots/foo.png`.
+//!
+//! To keep a history of the most recent scores of each reference, e.g. to notice a test
+//! trending towards its threshold, set `TWENTY_TWENTY_SCORE_HISTORY` to the number of scores to
+//! keep. They are written next to the reference, so `foo.png` has its history in
+//! `foo.scores.json`.
+//!
+//! To catch references that were corrupted, e.g. truncated by a bad merge, set
+//! `TWENTY_TWENTY_CHECKSUMS=1`. Overwrite modes then write a checksum next to each reference,
+//! `foo.png` has its checksum in `foo.checksum`, and a reference that no longer matches its
+//! checksum fails its comparisons with a clear error.
+//!
+//! To get a JUnit XML report of every comparison, for CI dashboards, run with
+//! `TWENTY_TWENTY_JUNIT=report.xml`.
+//!
+//! To find slow snapshot tests, set `TWENTY_TWENTY_TIMINGS=1` to log how long each comparison
+//! spent decoding its reference and comparing the images. The JUnit report carries the same
+//! times.
+//!
+//! To tune a comparison without touching its test, put a TOML sidecar next to its reference, so
+//! `foo.png` is compared with the options in `foo.png.tt.toml`, like
+//!
+//! ```toml
+//! min = 0.98
+//! metric = "psnr"
+//! mask = "foo-mask.png"
+//! mask_regions = [{ x = 0, y = 0, width = 200, height = 24 }]
+//! ```
+//!
+//! Each option is optional and overrides what the test passed in, and the mask is relative to
+//! the sidecar. The differences within each of the mask regions are ignored, see
+//! [`Config::mask_region`].
 
 #![deny(missing_docs)]
 
+mod base64;
+mod bit_depth;
+mod channel;
+mod checksum;
+mod color_space;
+mod config;
+mod diff;
+mod environment;
+#[cfg(feature = "exr")]
+mod exr;
+mod git;
 #[cfg(feature = "h264")]
 mod h264;
+mod history;
+#[cfg(feature = "http")]
+mod http;
+mod manifest;
+mod metric;
+mod phase_correlation;
+mod prepare;
+#[cfg(feature = "indicatif")]
+mod progress;
+mod raw;
+mod report;
+mod sidecar;
+#[cfg(feature = "svg")]
+mod svg;
+mod symmetry;
+
+pub use bit_depth::BitDepth;
+pub use channel::Channel;
+pub use checksum::update_checksum;
+pub use color_space::ColorSpace;
+pub use config::Config;
+#[cfg(feature = "exr")]
+pub use exr::assert_exr;
 #[cfg(feature = "h264")]
-pub use h264::assert_h264_frame;
+pub use h264::{
+    assert_h264_animation, assert_h264_frame, assert_h264_frame_reader, assert_h264_frames, assert_h264_keyframes,
+    assert_video_file_frame, compare_videos, decode_h264_frame, decode_h264_frame_reader, decode_h264_frames,
+    decode_h264_keyframes, decode_video_file_frame, h264_frame_to_png, h264_frames_to_pngs, Plane, VideoComparison,
+};
+#[cfg(feature = "http")]
+pub use http::assert_image_url;
+pub use manifest::assert_from_manifest;
+pub use metric::{Metric, SimilarityMap};
+pub use report::{collect_results, flush_reports, print_summary, set_result_callback, take_results, ComparisonRecord};
+#[cfg(feature = "svg")]
+pub use svg::assert_image_against_svg;
+pub use symmetry::{assert_self_symmetry, Axis};
 
 const CRATE_ENV_VAR: &str = "TWENTY_TWENTY";
+const SNAPSHOT_DIR_ENV_VAR: &str = "TWENTY_TWENTY_SNAPSHOT_DIR";
+const STRICT_ENV_VAR: &str = "TWENTY_TWENTY_STRICT";
+const ONLY_ENV_VAR: &str = "TWENTY_TWENTY_ONLY";
 
-/// The different modes available for the TWENTY_TWENTY environment variable.
-#[derive(Default, PartialEq)]
-enum Mode {
+/// The different modes available for the TWENTY_TWENTY environment variable, for tools that
+/// need to read or set it the same way the crate does.
+///
+/// ```rust
+/// let mode: twenty_twenty::Mode = "store-artifact".parse().unwrap();
+/// assert_eq!(mode, twenty_twenty::Mode::StoreArtifact);
+/// assert_eq!(mode.to_string(), "store-artifact");
+/// assert_eq!(
+///     "compare-and-store".parse(),
+///     Ok(twenty_twenty::Mode::StoreArtifact)
+/// );
+/// ```
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Mode {
     /// Only assert the image diff is within the given threshold.
     #[default]
     Default,
     /// Overwrite the file we are comparing against, i.e. accept the changes of the diff.
     Overwrite,
-    /// Store the files on disk always (for now make all paths relative to `artifacts/`).
+    /// Store the files on disk always, under `artifacts/`, see [`artifact_path`], while still
+    /// failing comparisons that don't match, so a failing CI run leaves a complete set of
+    /// candidate references to review. `compare-and-store` selects this mode too.
     StoreArtifact,
-    /// Store the files on disk when they don't match (for now make all paths relative to `artifacts/`).
+    /// Store the files on disk when they don't match, under `artifacts/`, see [`artifact_path`].
     StoreArtifactOnMismatch,
+    /// Overwrite the file we are comparing against only when the images don't match, leaving
+    /// references that still match byte for byte untouched.
+    OverwriteOnMismatch,
+    /// Skip every comparison, passing without reading the reference, e.g. while regenerating
+    /// references in bulk.
+    Off,
+    /// Save the actual image next to the reference when they don't match, like `insta` does for
+    /// text snapshots, so `foo.png` gets a pending `foo.png.new`, along with what it scored in
+    /// `foo.png.new.json`, for the reviewer to accept. The comparison still fails.
+    Pending,
+}
+
+impl Mode {
+    /// Whether a missing reference should fail the comparison in this mode, rather than being
+    /// created.
+    fn requires_reference(&self, config: &Config) -> bool {
+        !matches!(self, Mode::OverwriteOnMismatch | Mode::Pending) && config.requires_reference()
+    }
+
+    /// Read the mode from the TWENTY_TWENTY environment variable, falling back to
+    /// [`Mode::Default`] if it is unset or not a mode.
+    pub fn from_env() -> Self {
+        let var = std::env::var_os(CRATE_ENV_VAR);
+        var.as_deref()
+            .and_then(std::ffi::OsStr::to_str)
+            .unwrap_or_default()
+            .parse()
+            .unwrap_or_default()
+    }
 }
 
+/// Parses the value of the TWENTY_TWENTY environment variable. Anything that isn't one of the
+/// modes is [`Mode::Default`], like it is for the crate itself.
 impl std::str::FromStr for Mode {
     type Err = std::convert::Infallible;
 
     fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
         Ok(match s {
             "overwrite" => Mode::Overwrite,
-            "store-artifact" => Mode::StoreArtifact,
+            "overwrite-on-mismatch" => Mode::OverwriteOnMismatch,
+            "store-artifact" | "compare-and-store" => Mode::StoreArtifact,
             "store-artifact-on-mismatch" => Mode::StoreArtifactOnMismatch,
+            "off" => Mode::Off,
+            "pending" => Mode::Pending,
             _ => Mode::Default,
         })
     }
 }
 
+/// Formats the mode as the value of the TWENTY_TWENTY environment variable that selects it.
+impl std::fmt::Display for Mode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Mode::Default => "default",
+            Mode::Overwrite => "overwrite",
+            Mode::OverwriteOnMismatch => "overwrite-on-mismatch",
+            Mode::StoreArtifact => "store-artifact",
+            Mode::StoreArtifactOnMismatch => "store-artifact-on-mismatch",
+            Mode::Off => "off",
+            Mode::Pending => "pending",
+        })
+    }
+}
+
 /// Compare the contents of the file to the image provided.
 /// If the two are less similar than the `min_permissible_similarity` threshold,
 /// the test will fail.
@@ -81,120 +253,2020 @@ impl std::str::FromStr for Mode {
 /// If the images are the exact same, the score will be 1.
 #[track_caller]
 pub fn assert_image<P: AsRef<std::path::Path>>(path: P, actual: &image::DynamicImage, min_permissible_similarity: f64) {
-    if let Err(e) = assert_image_impl(path, actual, min_permissible_similarity) {
+    Config::default().assert_image(path, actual, min_permissible_similarity)
+}
+
+/// Compare the contents of the file to the image provided, like [`assert_image`], and return the
+/// score on success, e.g. to log it or make further assertions on it.
+/// When no comparison is made, because the mode is `off` or `overwrite`, or the reference was
+/// rewritten by `overwrite-on-mismatch`, the score is that of identical images, like 1 for SSIM.
+#[track_caller]
+pub fn assert_image_scored<P: AsRef<std::path::Path>>(
+    path: P,
+    actual: &image::DynamicImage,
+    min_permissible_similarity: f64,
+) -> f64 {
+    Config::default().assert_image_scored(path, actual, min_permissible_similarity)
+}
+
+/// Compare the contents of each file to its image, like [`assert_image`], and fail listing
+/// every comparison that failed, rather than stopping at the first.
+/// Each case is the path of the reference, the actual image, and its
+/// `min_permissible_similarity`.
+/// With the `indicatif` feature, a progress bar with an ETA is shown on stderr while the
+/// comparisons run.
+#[track_caller]
+pub fn assert_images<P: AsRef<std::path::Path>>(cases: &[(P, &image::DynamicImage, f64)]) {
+    Config::default().assert_images(cases)
+}
+
+pub(crate) fn assert_images_impl<P: AsRef<std::path::Path>>(
+    cases: &[(P, &image::DynamicImage, f64)],
+    config: &Config,
+) -> anyhow::Result<()> {
+    #[cfg(feature = "indicatif")]
+    let progress = progress::bar(cases.len());
+
+    let mut failures = Vec::new();
+    for (path, actual, min_permissible_similarity) in cases {
+        if let Err(e) = assert_image_impl(path, actual, *min_permissible_similarity, config) {
+            failures.push(e.to_string());
+        }
+        #[cfg(feature = "indicatif")]
+        progress.inc(1);
+    }
+
+    #[cfg(feature = "indicatif")]
+    progress.finish_and_clear();
+
+    if !failures.is_empty() {
+        anyhow::bail!(
+            "{} of {} comparisons failed:\n{}",
+            failures.len(),
+            cases.len(),
+            failures.join("\n")
+        );
+    }
+
+    Ok(())
+}
+
+/// Compare the image provided to a reference stored as a directory of tiles, for images too large
+/// to comfortably decode as a single reference.
+/// The image is sliced into a grid of `tile_size` by `tile_size` tiles, where the tiles along
+/// the right and bottom edges may be smaller, and each tile is compared to `dir/tile-ROW-COL.png`
+/// like [`assert_image`]. Overwrite modes write the tiles, so
+/// `TWENTY_TWENTY=overwrite-on-mismatch` only rewrites the tiles that changed.
+/// If any tile is less similar than the `min_permissible_similarity` threshold, the test will
+/// fail, listing every tile that did.
+/// The `min_permissible_similarity` is a float between 0 and 1.
+/// If the images are the exact same, the score of every tile will be 1.
+#[track_caller]
+pub fn assert_image_tiled<P: AsRef<std::path::Path>>(
+    dir: P,
+    actual: &image::DynamicImage,
+    min_permissible_similarity: f64,
+    tile_size: u32,
+) {
+    Config::default().assert_image_tiled(dir, actual, min_permissible_similarity, tile_size)
+}
+
+pub(crate) fn assert_image_tiled_impl(
+    dir: &std::path::Path,
+    actual: &image::DynamicImage,
+    min_permissible_similarity: f64,
+    tile_size: u32,
+    config: &Config,
+) -> anyhow::Result<()> {
+    anyhow::ensure!(tile_size > 0, "tile size must be greater than 0");
+
+    let mut failures = Vec::new();
+    for (row, y) in (0..actual.height()).step_by(tile_size as usize).enumerate() {
+        for (col, x) in (0..actual.width()).step_by(tile_size as usize).enumerate() {
+            let tile = actual.crop_imm(
+                x,
+                y,
+                tile_size.min(actual.width() - x),
+                tile_size.min(actual.height() - y),
+            );
+            let path = dir.join(format!("tile-{row}-{col}.png"));
+            if let Err(e) = assert_image_impl(&path, &tile, min_permissible_similarity, config) {
+                failures.push(e.to_string());
+            }
+        }
+    }
+    if !failures.is_empty() {
+        anyhow::bail!(
+            "{} tiles of `{}` failed:\n{}",
+            failures.len(),
+            dir.display(),
+            failures.join("\n")
+        );
+    }
+
+    Ok(())
+}
+
+/// Compare the contents of the file to the image provided, like [`assert_image`], but on
+/// tokio's blocking thread pool so the file IO and comparison don't stall the async runtime of
+/// an async test harness.
+#[cfg(feature = "tokio")]
+pub async fn assert_image_async<P: AsRef<std::path::Path>>(
+    path: P,
+    actual: &image::DynamicImage,
+    min_permissible_similarity: f64,
+) {
+    Config::default()
+        .assert_image_async(path, actual, min_permissible_similarity)
+        .await
+}
+
+/// Compare the contents of the file to the image provided by their peak signal-to-noise ratio,
+/// the way video engineers measure codec quality.
+/// If the PSNR is less than `min_db` decibels, the test will fail.
+/// If the images are the exact same, the PSNR will be infinite.
+#[track_caller]
+pub fn assert_image_psnr<P: AsRef<std::path::Path>>(path: P, actual: &image::DynamicImage, min_db: f64) {
+    Config::new().metric(Metric::Psnr).assert_image(path, actual, min_db)
+}
+
+/// Compare the contents of the file to the image provided by the root-mean-square difference of
+/// their channels, normalized to `[0, 1]`, for error budgets given as a maximum error.
+/// If the RMS error is more than `max_rms`, the test will fail, reporting the error.
+/// If the images are the exact same, the RMS error will be 0.
+#[track_caller]
+pub fn assert_image_rms<P: AsRef<std::path::Path>>(path: P, actual: &image::DynamicImage, max_rms: f64) {
+    Config::new()
+        .metric(Metric::Rms)
+        .assert_image(path, actual, 1.0 - max_rms)
+}
+
+/// Compare the contents of the file to the image provided using the given [`Metric`].
+/// If the score is less than `min_score`, the test will fail.
+/// What a sensible minimum is depends on the metric.
+#[track_caller]
+pub fn assert_image_with_metric<P: AsRef<std::path::Path>>(
+    path: P,
+    actual: &image::DynamicImage,
+    min_score: f64,
+    metric: Metric,
+) {
+    Config::new().metric(metric).assert_image(path, actual, min_score)
+}
+
+/// Compare a single channel of the contents of the file to the same channel of the image
+/// provided, ignoring the others. See [`Config::channel`].
+/// If the two are less similar than the `min_permissible_similarity` threshold,
+/// the test will fail.
+/// The `min_permissible_similarity` is a float between 0 and 1.
+/// If the channels are the exact same, the score will be 1.
+#[track_caller]
+pub fn assert_image_channel<P: AsRef<std::path::Path>>(
+    path: P,
+    actual: &image::DynamicImage,
+    min_permissible_similarity: f64,
+    channel: Channel,
+) {
+    Config::new()
+        .channel(channel)
+        .assert_image(path, actual, min_permissible_similarity)
+}
+
+/// Compare the contents of the file to the image provided, weighting how much each pixel counts
+/// towards the score by a grayscale importance map the same size as the images. See
+/// [`Config::importance_map`].
+/// If the two are less similar than the `min_permissible_similarity` threshold,
+/// the test will fail.
+/// The `min_permissible_similarity` is a float between 0 and 1.
+/// If the images are the exact same, the score will be 1.
+#[track_caller]
+pub fn assert_image_weighted<P: AsRef<std::path::Path>>(
+    path: P,
+    actual: &image::DynamicImage,
+    min_permissible_similarity: f64,
+    importance_map: &image::GrayImage,
+) {
+    Config::new()
+        .importance_map(importance_map.clone())
+        .assert_image(path, actual, min_permissible_similarity)
+}
+
+/// Compare the contents of the file to a raw, tightly packed RGBA8 buffer of the given
+/// dimensions, like the ones GPU readbacks produce.
+/// If the two are less similar than the `min_permissible_similarity` threshold,
+/// the test will fail.
+/// The `min_permissible_similarity` is a float between 0 and 1.
+/// If the images are the exact same, the score will be 1.
+#[track_caller]
+pub fn assert_rgba8<P: AsRef<std::path::Path>>(
+    path: P,
+    width: u32,
+    height: u32,
+    actual: &[u8],
+    min_permissible_similarity: f64,
+) {
+    if let Err(e) = try_assert_rgba8(path, width, height, actual, min_permissible_similarity) {
+        panic!("assertion failed: {e}")
+    }
+}
+
+/// Compare the contents of the file to the image provided, like [`assert_image`], but return
+/// the failure as an error instead of panicking, and the score on success.
+/// Every failure, from a reference that can't be read or decoded to a comparison that errors
+/// or falls below the threshold, is returned as an error, so tools embedding the crate can map
+/// them into their own error type without catching panics.
+pub fn try_assert_image<P: AsRef<std::path::Path>>(
+    path: P,
+    actual: &image::DynamicImage,
+    min_permissible_similarity: f64,
+) -> anyhow::Result<f64> {
+    Config::default().try_assert_image(path, actual, min_permissible_similarity)
+}
+
+/// Compare the contents of the file to a raw, tightly packed RGBA8 buffer, like
+/// [`assert_rgba8`], but return the failure as an error instead of panicking, like
+/// [`try_assert_image`]. A buffer whose length doesn't match the dimensions is an error too.
+pub fn try_assert_rgba8<P: AsRef<std::path::Path>>(
+    path: P,
+    width: u32,
+    height: u32,
+    actual: &[u8],
+    min_permissible_similarity: f64,
+) -> anyhow::Result<f64> {
+    let expected_len = width as usize * height as usize * 4;
+    if actual.len() != expected_len {
+        anyhow::bail!(
+            "RGBA8 buffer is {} bytes but a {}x{} image needs {} bytes",
+            actual.len(),
+            width,
+            height,
+            expected_len
+        );
+    }
+    let Some(image) = image::RgbaImage::from_raw(width, height, actual.to_vec()) else {
+        anyhow::bail!("the container was not big enough as per: https://docs.rs/image/latest/image/struct.ImageBuffer.html#method.from_raw");
+    };
+    try_assert_image(
+        path,
+        &image::DynamicImage::ImageRgba8(image),
+        min_permissible_similarity,
+    )
+}
+
+/// Compare the contents of the file to the image provided pixel for pixel, for deterministic
+/// output where any difference at all is a bug that a perceptual metric would hide.
+/// If any pixel differs once both are converted to RGBA8, the test will fail, reporting the
+/// first differing pixel.
+#[track_caller]
+pub fn assert_image_exact<P: AsRef<std::path::Path>>(path: P, actual: &image::DynamicImage) {
+    if let Err(e) = assert_image_exact_impl(path.as_ref(), actual) {
         panic!("assertion failed: {e}")
     }
 }
 
+fn assert_image_exact_impl(path: &std::path::Path, actual: &image::DynamicImage) -> anyhow::Result<()> {
+    if Config::default().skipped() {
+        return Ok(());
+    }
+
+    let path = resolve_reference_path(path);
+    let result = compare_exactly(&path, actual);
+    report::record(&path, &result);
+    result
+}
+
+fn compare_exactly(path: &std::path::Path, actual: &image::DynamicImage) -> anyhow::Result<()> {
+    let mode = Mode::from_env();
+
+    if mode == Mode::Overwrite {
+        return save_reference(actual, path);
+    }
+
+    let expected = load_reference(path, actual, mode.requires_reference(&Config::default()))?;
+    let difference = first_difference(&rgba8(&expected), &rgba8(actual));
+    let image_mismatch = difference.is_some();
+    report::notify(path, if image_mismatch { 0.0 } else { 1.0 }, 1.0, !image_mismatch, mode);
+
+    if mode == Mode::OverwriteOnMismatch {
+        return if image_mismatch {
+            save_reference(actual, path)
+        } else {
+            Ok(())
+        };
+    }
+
+    let mut saved = String::new();
+    if mode == Mode::StoreArtifact || (mode == Mode::StoreArtifactOnMismatch && image_mismatch) {
+        let artifact = artifact_path(path);
+        save_image(actual, &artifact)?;
+        environment::write(&artifact)?;
+        saved.push_str(&format!("\n                saved actual to {}", artifact.display()));
+    }
+
+    if mode == Mode::Pending {
+        if image_mismatch {
+            save_pending(actual, path, Some(0.0), 1.0, &Config::default())?;
+            saved.push_str(&format!(
+                "\n                saved pending reference to {}",
+                pending_path(path).display()
+            ));
+        } else {
+            remove_pending(path)?;
+        }
+    }
+
+    if let Some(difference) = difference {
+        anyhow::bail!(
+            r#"image (`{}`) {}{}
+                set {}=overwrite if these changes are intentional"#,
+            path.display(),
+            difference,
+            saved,
+            CRATE_ENV_VAR
+        )
+    }
+
+    Ok(())
+}
+
+/// Describe the first difference between the images, if there is one.
+fn first_difference(expected: &image::RgbaImage, actual: &image::RgbaImage) -> Option<String> {
+    if expected.dimensions() != actual.dimensions() {
+        return Some(format!(
+            "is {}x{} but the actual image is {}x{}",
+            expected.width(),
+            expected.height(),
+            actual.width(),
+            actual.height()
+        ));
+    }
+    if expected.as_raw() == actual.as_raw() {
+        return None;
+    }
+
+    expected
+        .enumerate_pixels()
+        .zip(actual.pixels())
+        .find(|((_, _, expected), actual)| expected != actual)
+        .map(|((x, y, expected), actual)| {
+            format!(
+                "differs first at pixel ({x}, {y}), which is {:?} but the actual pixel is {:?}",
+                expected.0, actual.0
+            )
+        })
+}
+
 pub(crate) fn assert_image_impl<P: AsRef<std::path::Path>>(
     path: P,
     actual: &image::DynamicImage,
     min_permissible_similarity: f64,
+    config: &Config,
 ) -> anyhow::Result<()> {
-    let path = path.as_ref();
-    let var = std::env::var_os(CRATE_ENV_VAR);
-    let mode: Mode = var
-        .as_deref()
-        .and_then(std::ffi::OsStr::to_str)
-        .unwrap_or_default()
-        .parse()
-        .unwrap_or_default();
+    assert_image_scored_impl(path, actual, min_permissible_similarity, config).map(|_| ())
+}
+
+pub(crate) fn assert_image_scored_impl<P: AsRef<std::path::Path>>(
+    path: P,
+    actual: &image::DynamicImage,
+    min_permissible_similarity: f64,
+    config: &Config,
+) -> anyhow::Result<f64> {
+    if config.skipped() {
+        return Ok(config.metric.identical_score());
+    }
+
+    let path = resolve_reference_path(path.as_ref());
+    let mut timing = report::Timing::default();
+    let min_permissible_similarity = match config.thresholds {
+        Some((fail, warn)) if warn < fail => {
+            anyhow::bail!("the warning threshold `{warn}` is less than the failure threshold `{fail}`")
+        }
+        Some((fail, _)) => fail,
+        None => min_permissible_similarity,
+    };
+    let result = sidecar::load(&path, min_permissible_similarity, config).and_then(|sidecar| {
+        let (min_permissible_similarity, config) = match &sidecar {
+            Some((min_permissible_similarity, config)) => (*min_permissible_similarity, config),
+            None => (min_permissible_similarity, config),
+        };
+        compare_with_reference(&path, actual, min_permissible_similarity, config, &mut timing)
+    });
+    report::record_timed(&path, &result, Some(timing));
+    result
+}
+
+/// The warning for a score that passed but is in the band between the failure and warning
+/// [`Config::thresholds`], or `None` if it failed or there is no warning threshold.
+fn threshold_warning(
+    path: &std::path::Path,
+    score: f64,
+    min_permissible_similarity: f64,
+    config: &Config,
+) -> Option<String> {
+    let (_, warn) = config.thresholds?;
+    (score >= min_permissible_similarity && score < warn).then(|| {
+        format!(
+            "image (`{}`) passed but its score `{}` is less than the warning threshold `{}`",
+            path.display(),
+            score,
+            warn
+        )
+    })
+}
+
+/// Resolve the path of a reference against `TWENTY_TWENTY_SNAPSHOT_DIR`, if it is set.
+fn resolve_reference_path(path: &std::path::Path) -> std::path::PathBuf {
+    match std::env::var_os(SNAPSHOT_DIR_ENV_VAR) {
+        Some(dir) if !dir.is_empty() => std::path::Path::new(&dir).join(path),
+        _ => path.to_path_buf(),
+    }
+}
+
+fn compare_with_reference(
+    path: &std::path::Path,
+    actual: &image::DynamicImage,
+    min_permissible_similarity: f64,
+    config: &Config,
+    timing: &mut report::Timing,
+) -> anyhow::Result<f64> {
+    let mode = Mode::from_env();
 
     if mode == Mode::Overwrite {
-        if let Err(e) = actual.save_with_format(path, image::ImageFormat::Png) {
-            panic!("unable to write image to {}: {}", path.display(), e);
+        // Still compare against the old reference, so accepting a wildly different image by
+        // accident doesn't go unnoticed.
+        let previous = if config.raw_reference {
+            raw::read(path).map(image::DynamicImage::ImageRgba8)
+        } else {
+            image::open(path).map_err(anyhow::Error::from)
+        };
+        if let Ok(expected) = previous {
+            if let Ok(score) = similarity(&expected, actual, config) {
+                if score < min_permissible_similarity {
+                    eprintln!(
+                        "twenty-twenty: accepting change to `{}` with score {} (large visual difference)",
+                        path.display(),
+                        score
+                    );
+                }
+            }
         }
-        return Ok(());
+        accept_reference(actual, path, config)?;
+        return Ok(config.metric.identical_score());
     }
 
-    // Treat a nonexistent file like an empty image.
-    let expected = match image::io::Reader::open(path) {
-        Ok(s) => s.decode().expect("decoding image from path failed"),
-        Err(e) => match e.kind() {
-            // We take the dimensions from the original image.
-            std::io::ErrorKind::NotFound => image::DynamicImage::new_rgba16(actual.width(), actual.height()),
-            _ => panic!("unable to read contents of {}: {}", path.display(), e),
-        },
-    };
+    let start = std::time::Instant::now();
+    let expected = load_configured_reference(path, actual, mode.requires_reference(config), config)?;
+    timing.decode += start.elapsed();
 
-    // Compare the two images.
-    let result = match image_compare::rgba_hybrid_compare(&expected.to_rgba8(), &actual.to_rgba8()) {
-        Ok(result) => result,
-        Err(err) => {
-            panic!("could not compare the images {err}")
+    // Compare the two images, unless a coarse comparison shows they're grossly different.
+    let start = std::time::Instant::now();
+    let approximate = fast_fail_estimate(&expected, actual, config)
+        .filter(|&estimate| estimate < FAST_FAIL_BOUND.min(min_permissible_similarity));
+    let mut score = match approximate {
+        Some(estimate) => Ok(estimate),
+        None => score(&expected, actual, min_permissible_similarity, config),
+    };
+    timing.compare += start.elapsed();
+    // Matching any of the older references is as good as matching the newest.
+    for historical in historical_reference_paths(path, config.history_size) {
+        if score.as_ref().is_ok_and(|score| *score >= min_permissible_similarity) {
+            break;
+        }
+        if !historical.exists() {
+            continue;
+        }
+        let start = std::time::Instant::now();
+        let expected = load_local_reference(&historical, actual, true, config)?;
+        timing.decode += start.elapsed();
+        let start = std::time::Instant::now();
+        if let Ok(historical_score) = similarity(&expected, actual, config) {
+            score = Ok(score.map_or(historical_score, |score| score.max(historical_score)));
+        }
+        timing.compare += start.elapsed();
+    }
+    let score = match score {
+        // Images that can't be compared at all, e.g. because their sizes differ, don't match.
+        Err(_) if mode == Mode::OverwriteOnMismatch => {
+            accept_reference(actual, path, config)?;
+            return Ok(config.metric.identical_score());
+        }
+        Err(e) if mode == Mode::Pending => {
+            save_pending(actual, path, None, min_permissible_similarity, config)?;
+            anyhow::bail!(
+                "{}\n                saved pending reference to {}",
+                e,
+                pending_path(path).display()
+            )
         }
+        result => result?,
     };
 
     // The SSIM score should be near 0, this is tweakable from the consumer, since they likely
     // have different thresholds.
-    let image_mismatch = result.score < min_permissible_similarity;
+    let image_mismatch = score < min_permissible_similarity;
+    let too_similar = config.max_similarity.is_some_and(|max| score > max);
+    report::notify(
+        path,
+        score,
+        min_permissible_similarity,
+        !image_mismatch && !too_similar,
+        mode,
+    );
+    if let Some(warning) = threshold_warning(path, score, min_permissible_similarity, config) {
+        eprintln!("twenty-twenty: {warning}");
+    }
+    if let Err(e) = history::record(path, score) {
+        eprintln!("twenty-twenty: {e}");
+    }
+
+    if mode == Mode::OverwriteOnMismatch {
+        if image_mismatch {
+            accept_reference(actual, path, config)?;
+            return Ok(config.metric.identical_score());
+        }
+        return Ok(score);
+    }
 
+    // Where everything saved for the comparison went, to point at it from the failure message.
+    let mut saved = String::new();
     if mode == Mode::StoreArtifact || (mode == Mode::StoreArtifactOnMismatch && image_mismatch) {
-        let artifact_path = std::path::Path::new("artifacts/").join(path);
-        if let Some(parent) = artifact_path.parent() {
-            std::fs::create_dir_all(parent)?;
+        let artifact = labelled_artifact_path(path, config.label.as_deref());
+        save_actual(actual, &artifact, config)?;
+        environment::write(&artifact)?;
+        saved.push_str(&format!("\n                saved actual to {}", artifact.display()));
+        if let Some(width) = config.thumbnail_width {
+            let thumbnail = artifact.with_extension("thumb.png");
+            save_image(&self::thumbnail(actual, width, config), &thumbnail)?;
+            saved.push_str(&format!("\n                saved thumbnail to {}", thumbnail.display()));
+        }
+        #[cfg(feature = "gif")]
+        if config.flicker && (expected.width(), expected.height()) == (actual.width(), actual.height()) {
+            let flicker = artifact.with_extension("flicker.gif");
+            diff::write_flicker_gif(&rgba8(&expected), &rgba8(actual), &flicker)?;
+            saved.push_str(&format!("\n                saved flicker GIF to {}", flicker.display()));
         }
-        if let Err(e) = actual.save_with_format(artifact_path, image::ImageFormat::Png) {
-            panic!("unable to write image to {}: {}", path.display(), e);
+    }
+
+    if mode == Mode::Pending {
+        let pending = pending_path(path);
+        if image_mismatch {
+            save_pending(actual, path, Some(score), min_permissible_similarity, config)?;
+            saved.push_str(&format!(
+                "\n                saved pending reference to {}",
+                pending.display()
+            ));
+        } else {
+            remove_pending(path)?;
         }
     }
 
+    // The diff lays the images over each other, so it can't be made when their sizes differ,
+    // which only metrics like the histogram allow.
+    if let Some(diff_path) = config
+        .diff_output
+        .as_ref()
+        .filter(|_| (expected.width(), expected.height()) == (actual.width(), actual.height()))
+    {
+        let diff = diff::triptych(&rgba8(&expected), &rgba8(actual));
+        save_image(&image::DynamicImage::ImageRgba8(diff), diff_path)?;
+        saved.push_str(&format!("\n                saved diff to {}", diff_path.display()));
+    }
+
+    // An older reference may have matched better than the coarse estimate of the newest.
+    if let Some(estimate) = approximate.filter(|&estimate| image_mismatch && estimate == score) {
+        anyhow::bail!(
+            r#"image (`{}`) is grossly different, with an approximate similarity of `{}` from a coarse comparison, so it wasn't compared in full{}
+                set {}=overwrite if these changes are intentional"#,
+            path.display(),
+            estimate,
+            saved,
+            CRATE_ENV_VAR
+        )
+    }
+
     if image_mismatch {
         anyhow::bail!(
-            r#"image (`{}`) score is `{}` which is less than min_permissible_similarity `{}`
+            r#"image (`{}`) {}{}{}
                 set {}=overwrite if these changes are intentional"#,
             path.display(),
-            result.score,
-            min_permissible_similarity,
+            config.metric.describe_mismatch(score, min_permissible_similarity),
+            mismatch_details(&expected, actual, config),
+            saved,
             CRATE_ENV_VAR
         )
     }
 
-    Ok(())
+    if let Some(max_similarity) = config.max_similarity.filter(|_| too_similar) {
+        anyhow::bail!(
+            "image (`{}`) {}, so the change it should show doesn't seem to have taken effect{}",
+            path.display(),
+            config.metric.describe_too_similar(score, max_similarity),
+            saved
+        )
+    }
+
+    Ok(score)
 }
 
-#[cfg(test)]
-mod tests {
-    use super::assert_image;
+/// Where the images differ most and how [`Config::auto_align`] shifted `actual`, to follow the
+/// description of a mismatch.
+fn mismatch_details(expected: &image::DynamicImage, actual: &image::DynamicImage, config: &Config) -> String {
+    let Ok(prepared) = prepare::prepare(config, expected, actual) else {
+        return String::new();
+    };
+    let mut details = String::new();
+    if prepared.shift != (0, 0) {
+        let (dx, dy) = prepared.shift;
+        details.push_str(&format!(
+            ", after aligning the actual image by shifting it ({dx}, {dy}) pixels"
+        ));
+    }
+    if let Some(r) = worst_region(&prepared, config) {
+        details.push_str(&format!(
+            ", largest difference around region x={}..{}, y={}..{}",
+            r.x,
+            r.x + r.width,
+            r.y,
+            r.y + r.height
+        ));
+    }
+    details
+}
 
-    #[test]
-    fn test_overwrite_mode() {
-        std::fs::create_dir_all("tests/tmp").unwrap();
-        std::fs::copy("tests/dog1.png", "tests/tmp/initial-grid.png").unwrap();
-        let expected_image = image::io::Reader::open("tests/initial-grid.png")
-            .unwrap()
-            .decode()
-            .unwrap();
-        std::env::set_var("TWENTY_TWENTY", "overwrite");
-        assert_image("tests/tmp/initial-grid.png", &expected_image, 1.0);
-        std::env::set_var("TWENTY_TWENTY", "");
-        assert_image("tests/tmp/initial-grid.png", &expected_image, 1.0);
+/// The region of the actual image as it was given that differs most, from the SSIM map of the
+/// prepared images, or `None` if they can't be compared pixel for pixel or the actual image was
+/// resampled so the region can't be mapped back onto it.
+fn worst_region(prepared: &prepare::Prepared<'_>, config: &Config) -> Option<Rect> {
+    let (origin_x, origin_y) = prepared.origin?;
+    let map = metric::similarity_map(&prepared.expected, &prepared.actual, config.color_space).ok()?;
+    let mut region = diff::worst_region(&map)?;
+    region.x = (region.x as i64 + origin_x).max(0) as u32;
+    region.y = (region.y as i64 + origin_y).max(0) as u32;
+    Some(region)
+}
+
+/// The image scaled down to `width` pixels wide, keeping its aspect ratio, or as it is if it is
+/// already no wider.
+fn thumbnail(image: &image::DynamicImage, width: u32, config: &Config) -> image::DynamicImage {
+    if image.width() <= width {
+        return image.clone();
     }
+    let height = ((image.height() as u64 * width as u64 / image.width() as u64) as u32).max(1);
+    image.resize_exact(width, height, config.filter())
+}
 
-    #[test]
-    fn test_store_artifact_mode() {
-        let expected_image = image::io::Reader::open("tests/initial-grid.png")
-            .unwrap()
-            .decode()
-            .unwrap();
-        std::env::set_var("TWENTY_TWENTY", "store-artifact");
-        assert_image("tests/initial-grid.png", &expected_image, 1.0);
-        std::env::set_var("TWENTY_TWENTY", "");
-        assert_image("artifacts/tests/initial-grid.png", &expected_image, 1.0);
+/// Save the image as the new reference at `path`, along with its checksum if those are on.
+fn save_reference(image: &image::DynamicImage, path: &std::path::Path) -> anyhow::Result<()> {
+    save_image(image, path)?;
+    checksum::write(path)
+}
+
+/// Save the image as the new reference at `path`, first shifting the older references kept by
+/// [`Config::history_size`] back one place and dropping the oldest.
+fn accept_reference(image: &image::DynamicImage, path: &std::path::Path, config: &Config) -> anyhow::Result<()> {
+    let history = historical_reference_paths(path, config.history_size);
+    for (i, older) in history.iter().enumerate().rev() {
+        let newer = if i == 0 { path } else { &history[i - 1] };
+        if newer.exists() {
+            std::fs::rename(newer, older).map_err(|e| {
+                anyhow::anyhow!(
+                    "unable to move reference {} to {}: {}",
+                    newer.display(),
+                    older.display(),
+                    e
+                )
+            })?;
+            checksum::write(older)?;
+        }
+    }
+    if config.raw_reference {
+        raw::save(image, path)?;
+        checksum::write(path)?;
+    } else {
+        save_reference(image, path)?;
     }
+    check_reference_size(path, config)
+}
 
-    #[test]
-    fn test_store_artifact_if_mismatch_mode() {
-        let expected_image = image::io::Reader::open("tests/initial-grid.png")
-            .unwrap()
-            .decode()
+/// Fail if the reference just written to `path` is larger than [`Config::max_reference_bytes`].
+/// The reference is left in place, to look at before shrinking it.
+fn check_reference_size(path: &std::path::Path, config: &Config) -> anyhow::Result<()> {
+    let Some(max_reference_bytes) = config.max_reference_bytes else {
+        return Ok(());
+    };
+    let len = std::fs::metadata(path)
+        .map_err(|e| anyhow::anyhow!("unable to read size of {}: {}", path.display(), e))?
+        .len();
+    if len > max_reference_bytes {
+        anyhow::bail!(
+            "reference (`{}`) is {} bytes, more than the {} bytes allowed, downscale or recompress it before \
+             committing it",
+            path.display(),
+            len,
+            max_reference_bytes
+        );
+    }
+    Ok(())
+}
+
+/// The paths of the older references kept next to the reference at `path` by
+/// [`Config::history_size`], newest first.
+fn historical_reference_paths(path: &std::path::Path, history_size: usize) -> Vec<std::path::PathBuf> {
+    (1..history_size)
+        .map(|n| match path.extension() {
+            Some(extension) => path.with_extension(format!("{n}.{}", extension.to_string_lossy())),
+            None => path.with_extension(n.to_string()),
+        })
+        .collect()
+}
+
+/// The path [`Mode::Pending`] saves the actual image to when it doesn't match the reference at
+/// `path`, `foo.png.new` for `foo.png`. The score it got is saved alongside it, in
+/// `foo.png.new.json`.
+pub fn pending_path<P: AsRef<std::path::Path>>(path: P) -> std::path::PathBuf {
+    let mut pending = path.as_ref().as_os_str().to_owned();
+    pending.push(".new");
+    std::path::PathBuf::from(pending)
+}
+
+/// Save the image as pending review to replace the reference at `path`, along with the score it
+/// got, if it could be compared at all.
+fn save_pending(
+    image: &image::DynamicImage,
+    path: &std::path::Path,
+    score: Option<f64>,
+    min_permissible_similarity: f64,
+    config: &Config,
+) -> anyhow::Result<()> {
+    let pending = pending_path(path);
+    save_actual(image, &pending, config)?;
+    write_pending_score(&pending, path, score, min_permissible_similarity)
+}
+
+/// Record the score the image saved pending review at `pending` got against the reference at
+/// `reference`, if it could be compared at all, next to it in `.new.json`.
+pub(crate) fn write_pending_score(
+    pending: &std::path::Path,
+    reference: &std::path::Path,
+    score: Option<f64>,
+    min_permissible_similarity: f64,
+) -> anyhow::Result<()> {
+    let metadata = serde_json::json!({
+        "reference": reference,
+        "score": score,
+        "min_permissible_similarity": min_permissible_similarity,
+    });
+    let metadata_path = pending.with_extension("new.json");
+    std::fs::write(&metadata_path, serde_json::to_string_pretty(&metadata)? + "\n")
+        .map_err(|e| anyhow::anyhow!("unable to write {}: {}", metadata_path.display(), e))
+}
+
+/// Remove any image left pending review for the reference at `path`, as it matches now.
+pub(crate) fn remove_pending(path: &std::path::Path) -> anyhow::Result<()> {
+    let pending = pending_path(path);
+    for file in [pending.with_extension("new.json"), pending] {
+        match std::fs::remove_file(&file) {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => {
+                anyhow::bail!("unable to remove {}: {}", file.display(), e)
+            }
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+/// Save the image in the format its extension names, like the reference it stands in for, or as
+/// a PNG if the extension names none, e.g. for pending references, creating any missing parent
+/// directories. Formats other than PNG need their `image` feature enabled.
+fn save_image(image: &image::DynamicImage, path: &std::path::Path) -> anyhow::Result<()> {
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    let format = image::ImageFormat::from_path(path).unwrap_or(image::ImageFormat::Png);
+    let result = match format {
+        // JPEG has no alpha channel to save.
+        image::ImageFormat::Jpeg => image::DynamicImage::ImageRgb8(image.to_rgb8()).save_with_format(path, format),
+        _ => image.save_with_format(path, format),
+    };
+    result.map_err(|e| anyhow::anyhow!("unable to write image to {}: {}", path.display(), e))
+}
+
+/// Save the actual image as an artifact or pending image at `path`, stored the same way as the
+/// reference it could replace, so raw if [`Config::raw_reference`] is set.
+fn save_actual(image: &image::DynamicImage, path: &std::path::Path, config: &Config) -> anyhow::Result<()> {
+    if config.raw_reference {
+        raw::save(image, path)
+    } else {
+        save_image(image, path)
+    }
+}
+
+/// Read an image saved by this crate, whether a reference, an artifact, or an image left
+/// pending, going by its contents rather than its extension, so raw images, see
+/// [`Config::raw_reference`], and pending images named `.new` are read too.
+pub fn read_saved_image<P: AsRef<std::path::Path>>(path: P) -> anyhow::Result<image::DynamicImage> {
+    let path = path.as_ref();
+    let bytes = std::fs::read(path).map_err(|e| anyhow::anyhow!("unable to read {}: {}", path.display(), e))?;
+    match image::load_from_memory(&bytes) {
+        Ok(image) => Ok(image),
+        Err(e) => raw::decode(&bytes)
+            .map(image::DynamicImage::ImageRgba8)
+            .map_err(|_| anyhow::anyhow!("unable to decode {}: {}", path.display(), e)),
+    }
+}
+
+/// Compare the reference image, decoded from the given bytes, to the image provided.
+/// This is useful for hermetic tests that embed their reference with `include_bytes!`.
+/// If the two are less similar than the `min_permissible_similarity` threshold,
+/// the test will fail.
+/// The `min_permissible_similarity` is a float between 0 and 1.
+/// If the images are the exact same, the score will be 1.
+/// As there is no file to write to, the `TWENTY_TWENTY` modes have no effect, and the comparison
+/// is reported to [`set_result_callback`] and [`take_results`] with the path `<bytes>`.
+#[track_caller]
+pub fn assert_image_against_bytes(reference: &[u8], actual: &image::DynamicImage, min_permissible_similarity: f64) {
+    if let Err(e) = assert_image_against_bytes_impl(reference, actual, min_permissible_similarity) {
+        panic!("assertion failed: {e}")
+    }
+}
+
+fn assert_image_against_bytes_impl(
+    reference: &[u8],
+    actual: &image::DynamicImage,
+    min_permissible_similarity: f64,
+) -> anyhow::Result<()> {
+    if Config::default().skipped() {
+        return Ok(());
+    }
+
+    let expected = image::load_from_memory(reference)
+        .map_err(|e| anyhow::anyhow!("unable to decode reference image from bytes: {e}"))?;
+
+    assert_against_unsaved_reference(
+        "<bytes>",
+        "doesn't match its reference bytes",
+        &expected,
+        actual,
+        min_permissible_similarity,
+    )
+}
+
+/// Score `actual` against a reference that has no file of its own, reporting the comparison
+/// under `name` in place of a path, and fail with the `mismatch` given if it is less similar
+/// than `min_permissible_similarity`.
+pub(crate) fn assert_against_unsaved_reference(
+    name: &str,
+    mismatch: &str,
+    expected: &image::DynamicImage,
+    actual: &image::DynamicImage,
+    min_permissible_similarity: f64,
+) -> anyhow::Result<()> {
+    let score = similarity(expected, actual, &Config::default())?;
+    let image_mismatch = score < min_permissible_similarity;
+    report::notify(
+        std::path::Path::new(name),
+        score,
+        min_permissible_similarity,
+        !image_mismatch,
+        Mode::from_env(),
+    );
+    if image_mismatch {
+        anyhow::bail!(
+            "image {}, {}",
+            mismatch,
+            Metric::Ssim.describe_mismatch(score, min_permissible_similarity)
+        )
+    }
+
+    Ok(())
+}
+
+/// Compare the reference image, decoded from the base64 provided, to the image provided, like
+/// [`assert_image_against_bytes`], for tiny references inlined in the test itself.
+/// The base64 is in the standard alphabet, and may be padded and wrapped across lines.
+/// If the two are less similar than the `min_permissible_similarity` threshold,
+/// the test will fail.
+/// The `min_permissible_similarity` is a float between 0 and 1.
+/// If the images are the exact same, the score will be 1.
+/// As there is no file to write to, the `TWENTY_TWENTY` modes have no effect.
+#[track_caller]
+pub fn assert_image_against_base64(reference: &str, actual: &image::DynamicImage, min_permissible_similarity: f64) {
+    let result = base64::decode(reference)
+        .map_err(|e| anyhow::anyhow!("unable to decode reference image from base64: {e}"))
+        .and_then(|reference| assert_image_against_bytes_impl(&reference, actual, min_permissible_similarity));
+    if let Err(e) = result {
+        panic!("assertion failed: {e}")
+    }
+}
+
+/// Compare the image provided to a reference generated by `generator`, which gives the expected
+/// color of the pixel at each `(x, y)` coordinate, for analytically defined patterns like
+/// gradients and checkerboards that don't need a file.
+/// The reference is generated at the size of `actual`.
+/// If the two are less similar than the `min_permissible_similarity` threshold,
+/// the test will fail.
+/// The `min_permissible_similarity` is a float between 0 and 1.
+/// If the images are the exact same, the score will be 1.
+/// As there is no file to write to, the `TWENTY_TWENTY` modes other than `off` have no effect,
+/// and the comparison is reported to [`set_result_callback`] and [`take_results`] with the path
+/// `<generated>`.
+#[track_caller]
+pub fn assert_image_against_fn<F: Fn(u32, u32) -> image::Rgba<u8>>(
+    generator: F,
+    actual: &image::DynamicImage,
+    min_permissible_similarity: f64,
+) {
+    if let Err(e) = assert_image_against_fn_impl(generator, actual, min_permissible_similarity) {
+        panic!("assertion failed: {e}")
+    }
+}
+
+fn assert_image_against_fn_impl<F: Fn(u32, u32) -> image::Rgba<u8>>(
+    generator: F,
+    actual: &image::DynamicImage,
+    min_permissible_similarity: f64,
+) -> anyhow::Result<()> {
+    if Config::default().skipped() {
+        return Ok(());
+    }
+
+    let expected =
+        image::DynamicImage::ImageRgba8(image::RgbaImage::from_fn(actual.width(), actual.height(), generator));
+    assert_against_unsaved_reference(
+        "<generated>",
+        "doesn't match its generated reference",
+        &expected,
+        actual,
+        min_permissible_similarity,
+    )
+}
+
+/// Check that the reference at `path` exists and decodes, returning its width and height, e.g.
+/// to fail fast on missing or corrupt references before running expensive renders.
+/// The reference is resolved against `TWENTY_TWENTY_SNAPSHOT_DIR` and checked against its
+/// checksum like it would be for a comparison.
+/// The `TWENTY_TWENTY` modes have no effect.
+pub fn validate_reference<P: AsRef<std::path::Path>>(path: P) -> anyhow::Result<(u32, u32)> {
+    let path = resolve_reference_path(path.as_ref());
+    checksum::verify(&path)?;
+    let reader = image::io::Reader::open(&path).map_err(|e| match e.kind() {
+        std::io::ErrorKind::NotFound => anyhow::anyhow!(
+            "image (`{}`) does not exist, set {}=overwrite to create it",
+            path.display(),
+            CRATE_ENV_VAR
+        ),
+        _ => anyhow::anyhow!("unable to read contents of {}: {}", path.display(), e),
+    })?;
+    let reference = reader.decode().map_err(|e| reference_decode_error(&path, e))?;
+    Ok((reference.width(), reference.height()))
+}
+
+/// Compare the contents of the file to the image provided, returning the similarity of each
+/// pixel along with their mean, for finding the worst region or computing your own statistics.
+/// Each pixel of the map is the SSIM of the 8x8 window it falls in, where 1 is identical, and
+/// their mean is the same score [`assert_image`] holds to its threshold.
+/// The `TWENTY_TWENTY` modes have no effect.
+pub fn compare_image_map<P: AsRef<std::path::Path>>(
+    path: P,
+    actual: &image::DynamicImage,
+) -> anyhow::Result<(f64, SimilarityMap)> {
+    let path = resolve_reference_path(path.as_ref());
+    let expected = load_reference(&path, actual, Config::default().requires_reference())?;
+    metric::ssim_map(&rgba8(&expected), &rgba8(actual))
+}
+
+/// The outcome of comparing an image to its reference, for assertions that make their own
+/// decisions about it, like applying a custom threshold or writing their own diff.
+#[derive(Clone, Debug)]
+pub struct Comparison {
+    /// The reference image, or an empty image if there is no reference yet.
+    pub expected: image::DynamicImage,
+    /// The image compared to the reference.
+    pub actual: image::DynamicImage,
+    /// The score of `actual` against `expected`, where higher is more similar. For SSIM, the
+    /// default, this is a float between 0 and 1.
+    pub score: f64,
+    /// The two images side by side with a heatmap of their differences, like
+    /// [`Config::diff_output`] writes. Only [`diff_references`] makes one, and not if the images
+    /// are different sizes.
+    pub diff: Option<image::RgbaImage>,
+}
+
+/// Compare the contents of the file to the image provided, without deciding whether the
+/// comparison passed. See [`Config::compare`] to change how the images are compared.
+/// The `TWENTY_TWENTY` modes have no effect.
+pub fn compare<P: AsRef<std::path::Path>>(path: P, actual: &image::DynamicImage) -> anyhow::Result<Comparison> {
+    Config::default().compare(path, actual)
+}
+
+fn compare_impl(path: &std::path::Path, actual: &image::DynamicImage, config: &Config) -> anyhow::Result<Comparison> {
+    let path = resolve_reference_path(path);
+    let expected = load_configured_reference(&path, actual, config.requires_reference(), config)?;
+    let score = similarity(&expected, actual, config)?;
+    Ok(Comparison {
+        expected,
+        actual: actual.clone(),
+        score,
+        diff: None,
+    })
+}
+
+/// Compare two versions of a reference to each other, rather than an image to its reference,
+/// to see how much an update to the references changed them.
+/// Both files must exist. The old version is the [`Comparison::expected`] image and the new
+/// one the [`Comparison::actual`] image, and the comparison includes a diff, e.g. to find the
+/// references that changed the most when reviewing an update to many of them at once.
+/// See [`Config::diff_references`] to change how they are compared.
+/// The `TWENTY_TWENTY` modes have no effect.
+pub fn diff_references<P: AsRef<std::path::Path>, Q: AsRef<std::path::Path>>(
+    old: P,
+    new: Q,
+) -> anyhow::Result<Comparison> {
+    Config::default().diff_references(old, new)
+}
+
+fn diff_references_impl(old: &std::path::Path, new: &std::path::Path, config: &Config) -> anyhow::Result<Comparison> {
+    let old = read_reference(&resolve_reference_path(old), config)?;
+    let new = read_reference(&resolve_reference_path(new), config)?;
+    let score = similarity(&old, &new, config)?;
+    let diff = ((old.width(), old.height()) == (new.width(), new.height()))
+        .then(|| diff::triptych(&rgba8(&old), &rgba8(&new)));
+    Ok(Comparison {
+        expected: old,
+        actual: new,
+        score,
+        diff,
+    })
+}
+
+/// Read the reference at `path`, which must exist, in the format the config stores references in.
+fn read_reference(path: &std::path::Path, config: &Config) -> anyhow::Result<image::DynamicImage> {
+    checksum::verify(path)?;
+    if config.raw_reference {
+        return raw::read(path).map(image::DynamicImage::ImageRgba8);
+    }
+    image::io::Reader::open(path)
+        .map_err(|e| anyhow::anyhow!("unable to read contents of {}: {}", path.display(), e))?
+        .decode()
+        .map_err(|e| reference_decode_error(path, e))
+}
+
+/// A rectangular region of an image, in pixels.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, serde::Deserialize)]
+pub struct Rect {
+    /// The column of the left edge.
+    pub x: u32,
+    /// The row of the top edge.
+    pub y: u32,
+    /// The width of the region.
+    pub width: u32,
+    /// The height of the region.
+    pub height: u32,
+}
+
+/// Compare the contents of the file to the image provided, tile by tile, so you can tell where
+/// in the image a difference is concentrated.
+/// The images are divided into a grid of `tile_size` by `tile_size` tiles, where the tiles along
+/// the right and bottom edges may be smaller, and the SSIM score of each tile is returned.
+/// Tiles should be comfortably larger than the 8x8 window SSIM is computed over.
+/// The `TWENTY_TWENTY` modes have no effect.
+pub fn compare_image_tiles<P: AsRef<std::path::Path>>(
+    path: P,
+    actual: &image::DynamicImage,
+    tile_size: u32,
+) -> anyhow::Result<Vec<(Rect, f64)>> {
+    anyhow::ensure!(tile_size > 0, "tile size must be greater than 0");
+
+    let path = resolve_reference_path(path.as_ref());
+    let expected = load_reference(&path, actual, Config::default().requires_reference())?;
+    if (expected.width(), expected.height()) != (actual.width(), actual.height()) {
+        anyhow::bail!(
+            "image (`{}`) is {}x{} but the actual image is {}x{}",
+            path.display(),
+            expected.width(),
+            expected.height(),
+            actual.width(),
+            actual.height()
+        );
+    }
+
+    let expected = rgba8(&expected);
+    let actual = rgba8(actual);
+    let mut tiles = Vec::new();
+    for y in (0..actual.height()).step_by(tile_size as usize) {
+        for x in (0..actual.width()).step_by(tile_size as usize) {
+            let rect = Rect {
+                x,
+                y,
+                width: tile_size.min(actual.width() - x),
+                height: tile_size.min(actual.height() - y),
+            };
+            let expected_tile = image::imageops::crop_imm(&*expected, x, y, rect.width, rect.height).to_image();
+            let actual_tile = image::imageops::crop_imm(&*actual, x, y, rect.width, rect.height).to_image();
+            tiles.push((rect, metric::ssim(&expected_tile, &actual_tile)?));
+        }
+    }
+
+    Ok(tiles)
+}
+
+/// Load the reference image at `path`, treating a nonexistent file like an empty image with the
+/// size and color type of `actual` unless `require_reference` is set.
+fn load_reference(
+    path: &std::path::Path,
+    actual: &image::DynamicImage,
+    require_reference: bool,
+) -> anyhow::Result<image::DynamicImage> {
+    checksum::verify(path)?;
+    Ok(match image::io::Reader::open(path) {
+        Ok(s) => {
+            let expected = s.decode().map_err(|e| reference_decode_error(path, e))?;
+            if expected.color() != actual.color() {
+                eprintln!(
+                    "twenty-twenty: reference `{}` is {:?} but the actual image is {:?}, both are converted to \
+                     RGBA8 to compare them, with opaque alpha for an image without any",
+                    path.display(),
+                    expected.color(),
+                    actual.color()
+                );
+            }
+            expected
+        }
+        Err(e) => match e.kind() {
+            std::io::ErrorKind::NotFound if require_reference => anyhow::bail!(
+                "image (`{}`) does not exist, set {}=overwrite to create it",
+                path.display(),
+                CRATE_ENV_VAR
+            ),
+            // We take the dimensions and color type from the original image.
+            std::io::ErrorKind::NotFound => image::DynamicImage::new(actual.width(), actual.height(), actual.color()),
+            _ => anyhow::bail!("unable to read contents of {}: {}", path.display(), e),
+        },
+    })
+}
+
+/// Load the reference image at `path` like [`load_reference`], but from `git HEAD` rather than
+/// the working tree if the config asks for it.
+fn load_configured_reference(
+    path: &std::path::Path,
+    actual: &image::DynamicImage,
+    require_reference: bool,
+    config: &Config,
+) -> anyhow::Result<image::DynamicImage> {
+    if !config.compare_against_head {
+        return load_local_reference(path, actual, require_reference, config);
+    }
+
+    match git::show_head(path)? {
+        Some(bytes) if bytes.starts_with(GIT_LFS_POINTER_PREFIX) => anyhow::bail!(
+            "reference at {} is a git-lfs pointer in git HEAD, which can't be compared against",
+            path.display()
+        ),
+        Some(bytes) if config.raw_reference => raw::decode(&bytes).map(image::DynamicImage::ImageRgba8).map_err(|e| {
+            anyhow::anyhow!(
+                "unable to decode raw reference (`{}`) from git HEAD: {}",
+                path.display(),
+                e
+            )
+        }),
+        Some(bytes) => image::load_from_memory(&bytes)
+            .map_err(|e| anyhow::anyhow!("unable to decode image (`{}`) from git HEAD: {}", path.display(), e)),
+        None if require_reference => anyhow::bail!(
+            "image (`{}`) is not committed to git HEAD, commit it to compare against it",
+            path.display()
+        ),
+        None => Ok(image::DynamicImage::new(
+            actual.width(),
+            actual.height(),
+            actual.color(),
+        )),
+    }
+}
+
+/// Load the reference at `path` from the working tree, as a PNG or, with
+/// [`Config::raw_reference`], as raw RGBA8.
+fn load_local_reference(
+    path: &std::path::Path,
+    actual: &image::DynamicImage,
+    require_reference: bool,
+    config: &Config,
+) -> anyhow::Result<image::DynamicImage> {
+    if config.raw_reference {
+        raw::load(path, actual, require_reference)
+    } else {
+        load_reference(path, actual, require_reference)
+    }
+}
+
+/// The first line of a git-lfs pointer file, which is checked out in place of the real file
+/// until `git lfs pull` is run.
+const GIT_LFS_POINTER_PREFIX: &[u8] = b"version https://git-lfs.github.com/spec/v1";
+
+/// Explain why the reference at `path` couldn't be decoded.
+fn reference_decode_error(path: &std::path::Path, e: impl std::fmt::Display) -> anyhow::Error {
+    if is_git_lfs_pointer(path) {
+        anyhow::anyhow!(
+            "reference at {} is an un-pulled git-lfs pointer; run `git lfs pull`",
+            path.display()
+        )
+    } else {
+        anyhow::anyhow!("unable to decode image (`{}`): {}", path.display(), e)
+    }
+}
+
+fn is_git_lfs_pointer(path: &std::path::Path) -> bool {
+    use std::io::Read;
+
+    let mut prefix = [0; GIT_LFS_POINTER_PREFIX.len()];
+    std::fs::File::open(path)
+        .and_then(|mut file| file.read_exact(&mut prefix))
+        .is_ok()
+        && &prefix[..] == GIT_LFS_POINTER_PREFIX
+}
+
+/// Compare an image to itself after a round trip, like encoding and decoding it, and return the
+/// SSIM score, for testing codecs without a stored reference.
+/// If the round-tripped image is a different size to the original, or less similar than the
+/// `min_permissible_similarity` threshold, the test will fail.
+/// The `min_permissible_similarity` is a float between 0 and 1.
+/// If the images are the exact same, the score will be 1.
+/// As there is no reference, the `TWENTY_TWENTY` modes have no effect.
+#[track_caller]
+pub fn assert_roundtrip(
+    original: &image::DynamicImage,
+    roundtripped: &image::DynamicImage,
+    min_permissible_similarity: f64,
+) -> f64 {
+    match assert_roundtrip_impl(original, roundtripped, min_permissible_similarity) {
+        Ok(score) => score,
+        Err(e) => panic!("assertion failed: {e}"),
+    }
+}
+
+fn assert_roundtrip_impl(
+    original: &image::DynamicImage,
+    roundtripped: &image::DynamicImage,
+    min_permissible_similarity: f64,
+) -> anyhow::Result<f64> {
+    if (original.width(), original.height()) != (roundtripped.width(), roundtripped.height()) {
+        anyhow::bail!(
+            "the round-tripped image is {}x{} but the original is {}x{}",
+            roundtripped.width(),
+            roundtripped.height(),
+            original.width(),
+            original.height()
+        );
+    }
+    let score = compare_images(original, roundtripped)?;
+    if score < min_permissible_similarity {
+        anyhow::bail!(
+            "round-tripped image {}",
+            Metric::Ssim.describe_mismatch(score, min_permissible_similarity)
+        );
+    }
+    Ok(score)
+}
+
+/// Compare two images and return their SSIM score, a float between 0 and 1.
+/// If the images are the exact same, the score will be 1.
+pub fn compare_images(expected: &image::DynamicImage, actual: &image::DynamicImage) -> anyhow::Result<f64> {
+    similarity(expected, actual, &Config::default())
+}
+
+/// The path the store-artifact modes save the actual image to when comparing against the
+/// reference at `path`.
+///
+/// Every artifact lands directly inside `artifacts/`, whether `path` is relative or absolute,
+/// with the reference path flattened into the file name: `%` is escaped as `%25`, `@` as `%40`,
+/// and path separators as `%2F` (`/`) and `%5C` (`\`), after dropping any leading `./`. So
+/// `tests/foo.png` is saved as `artifacts/tests%2Ffoo.png` and `/tmp/foo.png` as
+/// `artifacts/%2Ftmp%2Ffoo.png`. A [`Config::label`] goes before the extension after an `@`,
+/// with `.` in it escaped as `%2E`, so `tests/foo.png` labelled `case-3` is saved as
+/// `artifacts/tests%2Ffoo@case-3.png`. [`reference_path`] reverses the mapping.
+pub fn artifact_path<P: AsRef<std::path::Path>>(path: P) -> std::path::PathBuf {
+    labelled_artifact_path(path.as_ref(), None)
+}
+
+/// The path the store-artifact modes save the actual image to when comparing against the
+/// reference at `path`, with the label, if any, in the file name. See [`artifact_path`].
+fn labelled_artifact_path(path: &std::path::Path, label: Option<&str>) -> std::path::PathBuf {
+    let path = path.to_string_lossy();
+    let path = path.strip_prefix("./").unwrap_or(&path);
+    let extension_len = std::path::Path::new(path)
+        .extension()
+        .map_or(0, |extension| extension.len() + 1);
+    let (stem, extension) = path.split_at(path.len() - extension_len);
+
+    let mut name = String::with_capacity(path.len());
+    stem.chars().for_each(|c| push_escaped(c, &mut name));
+    if let Some(label) = label {
+        name.push('@');
+        for c in label.chars() {
+            match c {
+                // The label runs up to the extension, so it can't contain a `.` of its own.
+                '.' => name.push_str("%2E"),
+                c => push_escaped(c, &mut name),
+            }
+        }
+    }
+    extension.chars().for_each(|c| push_escaped(c, &mut name));
+    std::path::Path::new("artifacts").join(name)
+}
+
+/// Push the character to the artifact file name, escaping it if [`artifact_path`] gives it a
+/// special meaning.
+fn push_escaped(c: char, name: &mut String) {
+    match c {
+        '%' => name.push_str("%25"),
+        '@' => name.push_str("%40"),
+        '/' => name.push_str("%2F"),
+        '\\' => name.push_str("%5C"),
+        c => name.push(c),
+    }
+}
+
+/// The path of the reference an artifact at `path` was compared against, the inverse of
+/// [`artifact_path`], dropping any label. Returns `None` if `path` isn't inside `artifacts/`.
+pub fn reference_path<P: AsRef<std::path::Path>>(path: P) -> Option<std::path::PathBuf> {
+    let name = path
+        .as_ref()
+        .strip_prefix("artifacts")
+        .ok()?
+        .to_string_lossy()
+        .into_owned();
+    let mut reference = String::with_capacity(name.len());
+    let mut rest = name.as_str();
+    while let Some(c) = rest.chars().next() {
+        if c == '@' {
+            // Skip the label, which runs up to the extension.
+            rest = &rest[rest.find('.').unwrap_or(rest.len())..];
+            continue;
+        }
+        let (unescaped, len) = match rest.get(..3) {
+            Some("%25") => ('%', 3),
+            Some("%40") => ('@', 3),
+            Some("%2F") => ('/', 3),
+            Some("%5C") => ('\\', 3),
+            _ => (c, c.len_utf8()),
+        };
+        reference.push(unescaped);
+        rest = &rest[len..];
+    }
+    Some(std::path::PathBuf::from(reference))
+}
+
+/// Compare two images and return their peak signal-to-noise ratio in decibels.
+/// If the images are the exact same, the PSNR will be infinite.
+pub fn compare_images_psnr(expected: &image::DynamicImage, actual: &image::DynamicImage) -> anyhow::Result<f64> {
+    metric::psnr(&rgba8(expected), &rgba8(actual))
+}
+
+/// The coarse similarity below which [`Config::fast_fail`] fails a comparison without making it
+/// in full.
+const FAST_FAIL_BOUND: f64 = 0.5;
+
+/// The coarse estimate of the score for [`Config::fast_fail`], made on the images as they are
+/// prepared for comparison, so masks and the like are taken into account. There is no estimate
+/// for metrics it can't stand in for, for 16-bit comparisons, when a rotation of the actual image
+/// may still match, or if the images can't be prepared, which the full comparison reports.
+fn fast_fail_estimate(expected: &image::DynamicImage, actual: &image::DynamicImage, config: &Config) -> Option<f64> {
+    if !config.fast_fail
+        || !matches!(config.metric, Metric::Ssim | Metric::MsSsim | Metric::Rms)
+        || config.allow_rotations
+        || config.bit_depth.resolve(expected) == BitDepth::Sixteen
+    {
+        return None;
+    }
+    let prepared = prepare::prepare(config, expected, actual).ok()?;
+    metric::coarse_similarity(&prepared.expected, &prepared.actual)
+}
+
+/// Compute the score of `actual` against `expected`, and if it falls short and
+/// [`Config::allow_rotations`] is set, the best passing score of `actual` rotated by a quarter,
+/// half or three quarter turn.
+fn score(
+    expected: &image::DynamicImage,
+    actual: &image::DynamicImage,
+    min_permissible_similarity: f64,
+    config: &Config,
+) -> anyhow::Result<f64> {
+    let unrotated = prescreened_score(expected, actual, min_permissible_similarity, config);
+    if !config.allow_rotations
+        || unrotated
+            .as_ref()
+            .is_ok_and(|score| *score >= min_permissible_similarity)
+    {
+        return unrotated;
+    }
+
+    for degrees in [90, 180, 270] {
+        let rotated = match degrees {
+            90 => actual.rotate90(),
+            180 => actual.rotate180(),
+            _ => actual.rotate270(),
+        };
+        match prescreened_score(expected, &rotated, min_permissible_similarity, config) {
+            Ok(score) if score >= min_permissible_similarity => {
+                eprintln!(
+                    "twenty-twenty: the actual image only matches its reference once rotated {degrees} degrees \
+                     clockwise, with score {score}"
+                );
+                return Ok(score);
+            }
+            _ => {}
+        }
+    }
+    unrotated
+}
+
+/// Compute the score of `actual` against `expected`, prescreening at a lower resolution first if
+/// configured. The full-resolution pass is skipped when the low-resolution score is below the
+/// minimum, or more than halfway from the minimum to a perfect score.
+fn prescreened_score(
+    expected: &image::DynamicImage,
+    actual: &image::DynamicImage,
+    min_permissible_similarity: f64,
+    config: &Config,
+) -> anyhow::Result<f64> {
+    if let Some(target_dimension) = config.prescreen {
+        let same_size = (expected.width(), expected.height()) == (actual.width(), actual.height());
+        if same_size && expected.width().max(expected.height()) > target_dimension {
+            let filter = config.filter();
+            let coarse = similarity(
+                &expected.resize(target_dimension, target_dimension, filter),
+                &actual.resize(target_dimension, target_dimension, filter),
+                config,
+            )?;
+            let borderline = min_permissible_similarity + (1.0 - min_permissible_similarity) / 2.0;
+            if coarse < min_permissible_similarity || coarse >= borderline {
+                return Ok(coarse);
+            }
+        }
+    }
+
+    similarity(expected, actual, config)
+}
+
+/// Compute the score of `actual` against `expected` with the configured metric.
+fn similarity(expected: &image::DynamicImage, actual: &image::DynamicImage, config: &Config) -> anyhow::Result<f64> {
+    if config.bit_depth.resolve(expected) == BitDepth::Sixteen {
+        return bit_depth::score_sixteen(&expected.to_rgba16(), &actual.to_rgba16(), config);
+    }
+    let mut prepared = prepare::prepare(config, expected, actual)?;
+    if config.focus_on_changes {
+        prepare::focus_on_changes(&mut prepared);
+    }
+    let (expected, actual) = (&prepared.expected, &prepared.actual);
+    match &prepared.weights {
+        Some(weights) => config
+            .metric
+            .weighted_score(expected, actual, config.color_space, weights),
+        None => config
+            .metric
+            .score(expected, actual, config.color_space, config.filter()),
+    }
+}
+
+/// The image as RGBA8, borrowing it rather than copying it if it already is.
+fn rgba8(image: &image::DynamicImage) -> std::borrow::Cow<'_, image::RgbaImage> {
+    match image.as_rgba8() {
+        Some(image) => std::borrow::Cow::Borrowed(image),
+        None => std::borrow::Cow::Owned(image.to_rgba8()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::assert_image;
+
+    /// Serializes the tests that set, or compare under, the process-wide `TWENTY_TWENTY*` variables.
+    static ENV_LOCK: std::sync::Mutex<()> = std::sync::Mutex::new(());
+
+    fn env_lock() -> std::sync::MutexGuard<'static, ()> {
+        ENV_LOCK.lock().unwrap_or_else(|err| err.into_inner())
+    }
+
+    #[test]
+    fn test_overwrite_mode() {
+        let _env = env_lock();
+        std::fs::create_dir_all("tests/tmp").unwrap();
+        std::fs::copy("tests/dog1.png", "tests/tmp/initial-grid.png").unwrap();
+        let expected_image = image::io::Reader::open("tests/initial-grid.png")
+            .unwrap()
+            .decode()
+            .unwrap();
+        std::env::set_var("TWENTY_TWENTY", "overwrite");
+        assert_image("tests/tmp/initial-grid.png", &expected_image, 1.0);
+        std::env::set_var("TWENTY_TWENTY", "");
+        assert_image("tests/tmp/initial-grid.png", &expected_image, 1.0);
+    }
+
+    #[test]
+    fn test_overwrite_on_mismatch_mode() {
+        let _env = env_lock();
+        std::fs::create_dir_all("tests/tmp").unwrap();
+        std::fs::copy("tests/dog1.png", "tests/tmp/overwrite-on-mismatch.png").unwrap();
+        let original = std::fs::read("tests/tmp/overwrite-on-mismatch.png").unwrap();
+        let dog = image::io::Reader::open("tests/dog1.png").unwrap().decode().unwrap();
+        let grid = image::io::Reader::open("tests/initial-grid.png")
+            .unwrap()
+            .decode()
+            .unwrap();
+        std::env::set_var("TWENTY_TWENTY", "overwrite-on-mismatch");
+        // A matching image, even re-encoded, leaves the reference untouched.
+        assert_image("tests/tmp/overwrite-on-mismatch.png", &dog, 1.0);
+        assert_eq!(std::fs::read("tests/tmp/overwrite-on-mismatch.png").unwrap(), original);
+        assert_image("tests/tmp/overwrite-on-mismatch.png", &grid, 1.0);
+        std::env::set_var("TWENTY_TWENTY", "");
+        assert_image("tests/tmp/overwrite-on-mismatch.png", &grid, 1.0);
+    }
+
+    #[test]
+    fn test_tiled_overwrite() {
+        let _env = env_lock();
+        let actual = image::io::Reader::open("tests/dog1.png").unwrap().decode().unwrap();
+        std::env::set_var("TWENTY_TWENTY", "overwrite");
+        crate::assert_image_tiled("tests/tmp/tiled", &actual, 1.0, 64);
+        std::env::set_var("TWENTY_TWENTY", "");
+        assert!(std::path::Path::new("tests/tmp/tiled/tile-3-3.png").exists());
+        crate::assert_image_tiled("tests/tmp/tiled", &actual, 1.0, 64);
+    }
+
+    #[test]
+    fn test_store_artifact_mode() {
+        let _env = env_lock();
+        let expected_image = image::io::Reader::open("tests/initial-grid.png")
+            .unwrap()
+            .decode()
+            .unwrap();
+        std::env::set_var("TWENTY_TWENTY", "store-artifact");
+        assert_image("tests/initial-grid.png", &expected_image, 1.0);
+        std::env::set_var("TWENTY_TWENTY", "");
+        assert_image("artifacts/tests%2Finitial-grid.png", &expected_image, 1.0);
+    }
+
+    #[test]
+    fn test_store_artifact_if_mismatch_mode() {
+        let _env = env_lock();
+        let expected_image = image::io::Reader::open("tests/initial-grid.png")
+            .unwrap()
+            .decode()
             .unwrap();
         std::env::set_var("TWENTY_TWENTY", "store-artifact-on-mismatch");
-        // We expect the panic, so we just catch and continue on.
+        let err = crate::assert_image_impl(
+            "tests/multiple-frames.png",
+            &expected_image,
+            1.0,
+            &crate::Config::default(),
+        )
+        .unwrap_err()
+        .to_string();
+        std::env::set_var("TWENTY_TWENTY", "");
+        assert!(
+            err.contains("saved actual to artifacts/tests%2Fmultiple-frames.png"),
+            "{err}"
+        );
+        assert_image("artifacts/tests%2Fmultiple-frames.png", &expected_image, 1.0);
+        let environment: serde_json::Value =
+            serde_json::from_slice(&std::fs::read("artifacts/tests%2Fmultiple-frames.meta.json").unwrap()).unwrap();
+        assert_eq!(environment["os"], std::env::consts::OS);
+    }
+
+    #[cfg(feature = "gif")]
+    #[test]
+    fn test_flicker_gif() {
+        let _env = env_lock();
+        let actual = image::io::Reader::open("tests/dog1.png").unwrap().decode().unwrap();
+        std::env::set_var("TWENTY_TWENTY", "store-artifact");
+        crate::Config::new()
+            .flicker(true)
+            .assert_image("tests/dog1.png", &actual, 1.0);
+        std::env::set_var("TWENTY_TWENTY", "");
+        assert!(std::path::Path::new("artifacts/tests%2Fdog1.flicker.gif").exists());
+    }
+
+    #[test]
+    fn test_artifact_keeps_reference_format() {
+        let _env = env_lock();
+        std::fs::create_dir_all("tests/tmp").unwrap();
+        let expected = image::io::Reader::open("tests/initial-grid.png")
+            .unwrap()
+            .decode()
+            .unwrap();
+        expected.save("tests/tmp/artifact-format.bmp").unwrap();
+        let actual = image::io::Reader::open("tests/multiple-frames.png")
+            .unwrap()
+            .decode()
+            .unwrap();
+        std::env::set_var("TWENTY_TWENTY", "store-artifact-on-mismatch");
+        let result = crate::assert_image_impl("tests/tmp/artifact-format.bmp", &actual, 1.0, &crate::Config::default());
+        std::env::set_var("TWENTY_TWENTY", "");
+        assert!(result.is_err());
+        let artifact = image::io::Reader::open("artifacts/tests%2Ftmp%2Fartifact-format.bmp")
+            .unwrap()
+            .with_guessed_format()
+            .unwrap();
+        assert_eq!(artifact.format(), Some(image::ImageFormat::Bmp));
+    }
+
+    #[test]
+    fn test_threshold_warning() {
+        let path = std::path::Path::new("tests/dog1.png");
+        let config = super::Config::new().thresholds(0.9, 0.95);
+        let warning = super::threshold_warning(path, 0.92, 0.9, &config).unwrap();
+        assert!(warning.contains("is less than the warning threshold `0.95`"));
+        assert_eq!(super::threshold_warning(path, 0.97, 0.9, &config), None);
+        assert_eq!(super::threshold_warning(path, 0.85, 0.9, &config), None);
+        assert_eq!(super::threshold_warning(path, 0.92, 0.9, &super::Config::new()), None);
+    }
+
+    #[test]
+    fn test_base64() {
+        assert_eq!(crate::base64::decode("TWFu").unwrap(), b"Man");
+        assert_eq!(crate::base64::decode("TWE=").unwrap(), b"Ma");
+        assert_eq!(crate::base64::decode("TW\nE").unwrap(), b"Ma");
+        assert_eq!(crate::base64::decode("+/8=").unwrap(), [0xfb, 0xff]);
+        assert!(crate::base64::decode("TWFuT").is_err());
+        assert!(crate::base64::decode("TW-u").is_err());
+    }
+
+    #[test]
+    fn test_summary() {
+        let score = |path: &str, score, passed| crate::report::ComparisonRecord {
+            path: path.into(),
+            score,
+            threshold: 0.9,
+            passed,
+            mode: crate::Mode::Default,
+            timestamp: std::time::SystemTime::now(),
+        };
+        let summary = crate::report::render_summary(&[
+            score("tests/a.png", 0.99, true),
+            score("tests/b.png", 0.5, false),
+            score("tests/c.png", 0.95, true),
+        ]);
+        assert_eq!(
+            summary,
+            "twenty-twenty: 3 comparisons, 1 failed\n  FAIL      0.5000  tests/b.png\n  pass      0.9500  \
+             tests/c.png\n  pass      0.9900  tests/a.png\n"
+        );
+    }
+
+    #[test]
+    fn test_thumbnail() {
+        let _env = env_lock();
+        let actual = image::io::Reader::open("tests/initial-grid.png")
+            .unwrap()
+            .decode()
+            .unwrap();
+        std::env::set_var("TWENTY_TWENTY", "store-artifact");
+        crate::Config::new()
+            .thumbnail(256)
+            .assert_image("tests/initial-grid.png", &actual, 1.0);
+        std::env::set_var("TWENTY_TWENTY", "");
+        let thumbnail = image::open("artifacts/tests%2Finitial-grid.thumb.png").unwrap();
+        assert_eq!((thumbnail.width(), thumbnail.height()), (256, 144));
+    }
+
+    #[test]
+    fn test_pending_mode() {
+        let _env = env_lock();
+        std::fs::create_dir_all("tests/tmp").unwrap();
+        std::fs::copy("tests/initial-grid.png", "tests/tmp/pending.png").unwrap();
+        let actual = image::io::Reader::open("tests/multiple-frames.png")
+            .unwrap()
+            .decode()
+            .unwrap();
+        std::env::set_var("TWENTY_TWENTY", "pending");
+        let result = crate::assert_image_impl("tests/tmp/pending.png", &actual, 1.0, &crate::Config::default());
+        std::env::set_var("TWENTY_TWENTY", "");
+        assert!(result.is_err());
+        assert!(std::path::Path::new("tests/tmp/pending.png.new.json").exists());
+        let pending = image::io::Reader::open("tests/tmp/pending.png.new")
+            .unwrap()
+            .with_guessed_format()
+            .unwrap()
+            .decode()
+            .unwrap();
+        assert_eq!(crate::compare_images(&pending, &actual).unwrap(), 1.0);
+    }
+
+    #[test]
+    fn test_off_mode() {
+        let _env = env_lock();
+        std::fs::create_dir_all("tests/tmp").unwrap();
+        std::fs::copy("tests/initial-grid.png", "tests/tmp/off.png").unwrap();
+        let _ = std::fs::remove_file("tests/tmp/off-report.xml");
+        let _ = std::fs::remove_file(crate::artifact_path("tests/tmp/off.png"));
+        let actual = image::io::Reader::open("tests/dog1.png").unwrap().decode().unwrap();
+        std::env::set_var("TWENTY_TWENTY", "off");
+        std::env::set_var("TWENTY_TWENTY_JUNIT", "tests/tmp/off-report.xml");
+        assert_image("tests/tmp/off.png", &actual, 1.0);
+        crate::Config::new().assert_image("tests/tmp/off.png", &actual, 1.0);
+        std::env::remove_var("TWENTY_TWENTY_JUNIT");
+        std::env::set_var("TWENTY_TWENTY", "");
+
+        // Neither comparison was recorded or left an artifact behind.
+        assert!(!std::path::Path::new("tests/tmp/off-report.xml").exists());
+        assert!(!crate::artifact_path("tests/tmp/off.png").exists());
+    }
+
+    #[test]
+    fn test_only_tags() {
+        let _env = env_lock();
+        let actual = image::io::Reader::open("tests/dog1.png").unwrap().decode().unwrap();
+        std::env::set_var("TWENTY_TWENTY_ONLY", "critical");
+        assert_image("tests/initial-grid.png", &actual, 1.0);
+        let passed = crate::assert_image_impl(
+            "tests/initial-grid.png",
+            &actual,
+            1.0,
+            &crate::Config::new().tag("critical"),
+        )
+        .is_ok();
+        std::env::remove_var("TWENTY_TWENTY_ONLY");
+        assert!(!passed);
+    }
+
+    #[test]
+    fn test_snapshot_dir() {
+        let _env = env_lock();
+        let expected_image = image::io::Reader::open("tests/initial-grid.png")
+            .unwrap()
+            .decode()
+            .unwrap();
+        std::env::set_var("TWENTY_TWENTY_SNAPSHOT_DIR", "tests");
+        assert_image("initial-grid.png", &expected_image, 1.0);
+        std::env::remove_var("TWENTY_TWENTY_SNAPSHOT_DIR");
+    }
+
+    #[test]
+    fn test_artifact_path_round_trip() {
+        let artifact = crate::artifact_path("tests/initial-grid.png");
+        assert_eq!(artifact, std::path::Path::new("artifacts/tests%2Finitial-grid.png"));
+        assert_eq!(
+            crate::reference_path(artifact).unwrap(),
+            std::path::Path::new("tests/initial-grid.png")
+        );
+
+        let artifact = crate::labelled_artifact_path(std::path::Path::new("tests/foo@2x.png"), Some("case.3"));
+        assert_eq!(
+            artifact,
+            std::path::Path::new("artifacts/tests%2Ffoo%402x@case%2E3.png")
+        );
+        assert_eq!(
+            crate::reference_path(artifact).unwrap(),
+            std::path::Path::new("tests/foo@2x.png")
+        );
+
+        let artifact = crate::artifact_path("/tmp/100%/./dog.png");
+        assert_eq!(
+            artifact,
+            std::path::Path::new("artifacts/%2Ftmp%2F100%25%2F.%2Fdog.png")
+        );
+        assert_eq!(
+            crate::reference_path(artifact).unwrap(),
+            std::path::Path::new("/tmp/100%/./dog.png")
+        );
+    }
+
+    #[cfg(feature = "h264")]
+    #[test]
+    fn test_h264_animation_overwrite() {
+        let _env = env_lock();
+        std::fs::create_dir_all("tests/tmp").unwrap();
+        let actual = std::fs::read("tests/multiple-frames.h264").unwrap();
+        std::env::set_var("TWENTY_TWENTY", "overwrite");
+        crate::assert_h264_animation("tests/tmp/multiple-frames.apng", &actual, 1.0);
+        std::env::set_var("TWENTY_TWENTY", "");
+        crate::assert_h264_animation("tests/tmp/multiple-frames.apng", &actual, 1.0);
+    }
+
+    #[cfg(feature = "h264")]
+    #[test]
+    fn test_h264_animation_environment() {
+        let _env = env_lock();
+        std::fs::create_dir_all("tests/tmp").unwrap();
+        let actual = std::fs::read("tests/multiple-frames.h264").unwrap();
+        std::env::set_var("TWENTY_TWENTY", "overwrite");
+        crate::assert_h264_animation("tests/tmp/animation-environment.apng", &actual, 1.0);
+        std::env::set_var("TWENTY_TWENTY", "store-artifact");
+        crate::assert_h264_animation("tests/tmp/animation-environment.apng", &actual, 1.0);
+        std::env::set_var("TWENTY_TWENTY", "");
+        let environment: serde_json::Value =
+            serde_json::from_slice(&std::fs::read("artifacts/tests%2Ftmp%2Fanimation-environment.meta.json").unwrap())
+                .unwrap();
+        assert!(environment["ffmpeg"].is_string());
+    }
+
+    #[cfg(feature = "h264")]
+    #[test]
+    fn test_h264_keyframes_overwrite() {
+        let _env = env_lock();
+        std::fs::create_dir_all("tests/tmp").unwrap();
+        let actual = std::fs::read("tests/multiple-frames.h264").unwrap();
+        assert!(!crate::decode_h264_keyframes(&actual).unwrap().is_empty());
+        std::env::set_var("TWENTY_TWENTY", "overwrite");
+        crate::assert_h264_keyframes("tests/tmp/multiple-frames-keyframes.apng", &actual, 1.0);
+        std::env::set_var("TWENTY_TWENTY", "");
+        crate::assert_h264_keyframes("tests/tmp/multiple-frames-keyframes.apng", &actual, 1.0);
+    }
+
+    #[cfg(feature = "h264")]
+    #[test]
+    fn test_h264_keyframes_skip_frames() {
+        use image::AnimationDecoder;
+
+        let _env = env_lock();
+        std::fs::create_dir_all("tests/tmp").unwrap();
+        // The keyframes of the clip are its first and tenth frames, so skipping two frames only
+        // skips the first of them.
+        let actual = std::fs::read("tests/multiple-frames.h264").unwrap();
+        std::env::set_var("TWENTY_TWENTY", "overwrite");
+        crate::Config::new()
+            .skip_frames(2)
+            .assert_h264_keyframes("tests/tmp/skip-frames-keyframes.apng", &actual, 1.0);
+        std::env::set_var("TWENTY_TWENTY", "");
+        let file = std::io::BufReader::new(std::fs::File::open("tests/tmp/skip-frames-keyframes.apng").unwrap());
+        let decoder = image::codecs::png::PngDecoder::new(file).unwrap();
+        let keyframes = decoder.apng().unwrap().into_frames().collect_frames().unwrap();
+        assert_eq!(keyframes.len(), 1);
+    }
+
+    #[test]
+    fn test_corrupt_reference() {
+        let _env = env_lock();
+        std::fs::create_dir_all("tests/tmp").unwrap();
+        let png = std::fs::read("tests/dog1.png").unwrap();
+        std::fs::write("tests/tmp/truncated.png", &png[..png.len() / 2]).unwrap();
+        let expected_image = image::io::Reader::open("tests/dog1.png").unwrap().decode().unwrap();
+
+        let err = super::assert_image_impl(
+            "tests/tmp/truncated.png",
+            &expected_image,
+            1.0,
+            &super::Config::default(),
+        )
+        .unwrap_err();
+        assert!(err.to_string().contains("tests/tmp/truncated.png"));
+    }
+
+    #[test]
+    fn test_try_assert_errors() {
+        let _env = env_lock();
+        std::fs::create_dir_all("tests/tmp").unwrap();
+        std::fs::write("tests/tmp/not-a-png.png", b"not a png").unwrap();
+        let actual = image::io::Reader::open("tests/dog1.png").unwrap().decode().unwrap();
+
+        let err = crate::try_assert_image("tests/tmp/not-a-png.png", &actual, 0.0).unwrap_err();
+        assert!(err.to_string().contains("unable to decode image"));
+        let err = crate::try_assert_rgba8("tests/dog1.png", 2, 2, &[0; 4], 0.0).unwrap_err();
+        assert!(err
+            .to_string()
+            .contains("RGBA8 buffer is 4 bytes but a 2x2 image needs 16 bytes"));
+        let err = crate::try_assert_rgba8("tests/dog1.png", 1, 1, &[0; 4], 0.0).unwrap_err();
+        assert!(err.to_string().contains("could not compare the images"));
+    }
+
+    #[test]
+    fn test_git_lfs_pointer_reference() {
+        let _env = env_lock();
+        std::fs::create_dir_all("tests/tmp").unwrap();
+        std::fs::write(
+            "tests/tmp/lfs-pointer.png",
+            "version https://git-lfs.github.com/spec/v1\noid sha256:4d7a214614ab2935c943f9e0ff69d22eadbb8f32b1258daaa5e2ca24d17e2393\nsize 12345\n",
+        )
+        .unwrap();
+        let expected_image = image::io::Reader::open("tests/dog1.png").unwrap().decode().unwrap();
+
+        let err = super::assert_image_impl(
+            "tests/tmp/lfs-pointer.png",
+            &expected_image,
+            1.0,
+            &super::Config::default(),
+        )
+        .unwrap_err();
+        assert!(err.to_string().contains("git lfs pull"));
+    }
+
+    #[test]
+    fn test_history_size() {
+        let _env = env_lock();
+        std::fs::create_dir_all("tests/tmp").unwrap();
+        let _ = std::fs::remove_file("tests/tmp/history-size.1.png");
+        let dog = image::io::Reader::open("tests/dog1.png").unwrap().decode().unwrap();
+        let grid = image::io::Reader::open("tests/initial-grid.png")
+            .unwrap()
+            .decode()
+            .unwrap();
+        let config = crate::Config::new().history_size(2);
+        std::env::set_var("TWENTY_TWENTY", "overwrite");
+        config.assert_image("tests/tmp/history-size.png", &dog, 1.0);
+        config.assert_image("tests/tmp/history-size.png", &grid, 1.0);
+        std::env::set_var("TWENTY_TWENTY", "");
+        assert!(std::path::Path::new("tests/tmp/history-size.1.png").exists());
+        config.assert_image("tests/tmp/history-size.png", &dog, 1.0);
+        config.assert_image("tests/tmp/history-size.png", &grid, 1.0);
+    }
+
+    #[test]
+    fn test_raw_reference() {
+        let _env = env_lock();
+        std::fs::create_dir_all("tests/tmp").unwrap();
+        let actual = image::io::Reader::open("tests/dog1.png").unwrap().decode().unwrap();
+        let config = crate::Config::new().raw_reference(true);
+        std::env::set_var("TWENTY_TWENTY", "overwrite");
+        config.assert_image("tests/tmp/raw.rgba", &actual, 1.0);
+        std::env::set_var("TWENTY_TWENTY", "");
+        let raw = crate::raw::read(std::path::Path::new("tests/tmp/raw.rgba")).unwrap();
+        assert_eq!(raw.as_raw(), actual.to_rgba8().as_raw());
+        config.assert_image("tests/tmp/raw.rgba", &actual, 1.0);
+
+        std::env::set_var("TWENTY_TWENTY", "store-artifact");
+        config.assert_image("tests/tmp/raw.rgba", &actual, 1.0);
+        std::env::set_var("TWENTY_TWENTY", "");
+        let artifact = crate::read_saved_image(crate::artifact_path("tests/tmp/raw.rgba")).unwrap();
+        assert_eq!(artifact.to_rgba8().as_raw(), actual.to_rgba8().as_raw());
+        assert!(crate::raw::read(&crate::artifact_path("tests/tmp/raw.rgba")).is_ok());
+    }
+
+    #[test]
+    fn test_max_reference_bytes() {
+        let _env = env_lock();
+        std::fs::create_dir_all("tests/tmp").unwrap();
+        let actual = image::io::Reader::open("tests/dog1.png").unwrap().decode().unwrap();
+        std::env::set_var("TWENTY_TWENTY", "overwrite");
+        let err = crate::assert_image_impl(
+            "tests/tmp/max-reference-bytes.png",
+            &actual,
+            1.0,
+            &crate::Config::new().max_reference_bytes(1024),
+        )
+        .unwrap_err()
+        .to_string();
+        std::env::set_var("TWENTY_TWENTY", "");
+        assert!(err.contains("more than the 1024 bytes allowed"), "{err}");
+    }
+
+    #[test]
+    fn test_score_history() {
+        let _env = env_lock();
+        std::fs::create_dir_all("tests/tmp").unwrap();
+        std::fs::copy("tests/dog1.png", "tests/tmp/history.png").unwrap();
+        let _ = std::fs::remove_file("tests/tmp/history.scores.json");
+        let expected_image = image::io::Reader::open("tests/dog1.png").unwrap().decode().unwrap();
+        std::env::set_var("TWENTY_TWENTY_SCORE_HISTORY", "2");
+        for _ in 0..3 {
+            assert_image("tests/tmp/history.png", &expected_image, 1.0);
+        }
+        std::env::remove_var("TWENTY_TWENTY_SCORE_HISTORY");
+
+        let history: Vec<serde_json::Value> =
+            serde_json::from_str(&std::fs::read_to_string("tests/tmp/history.scores.json").unwrap()).unwrap();
+        assert_eq!(history.len(), 2);
+        assert_eq!(history[1]["score"], 1.0);
+    }
+
+    #[test]
+    fn test_checksum() {
+        let _env = env_lock();
+        std::fs::create_dir_all("tests/tmp").unwrap();
+        let actual = image::io::Reader::open("tests/dog1.png").unwrap().decode().unwrap();
+        std::env::set_var("TWENTY_TWENTY_CHECKSUMS", "1");
+        std::env::set_var("TWENTY_TWENTY", "overwrite");
+        assert_image("tests/tmp/checksum.png", &actual, 1.0);
+        std::env::set_var("TWENTY_TWENTY", "");
+        assert!(crate::checksum::checksum_path(std::path::Path::new("tests/tmp/checksum.png")).exists());
+        assert_image("tests/tmp/checksum.png", &actual, 1.0);
+
+        let bytes = std::fs::read("tests/tmp/checksum.png").unwrap();
+        std::fs::write("tests/tmp/checksum.png", &bytes[..bytes.len() / 2]).unwrap();
+        let err = crate::assert_image_impl("tests/tmp/checksum.png", &actual, 1.0, &crate::Config::default())
+            .unwrap_err()
+            .to_string();
+        std::env::remove_var("TWENTY_TWENTY_CHECKSUMS");
+        assert!(err.contains("failed integrity check"), "{err}");
+    }
+
+    #[test]
+    fn test_junit_report() {
+        let _env = env_lock();
+        std::fs::create_dir_all("tests/tmp").unwrap();
+        let expected_image = image::io::Reader::open("tests/initial-grid.png")
+            .unwrap()
+            .decode()
+            .unwrap();
+        std::env::set_var("TWENTY_TWENTY_JUNIT", "tests/tmp/report.xml");
+        assert_image("tests/initial-grid.png", &expected_image, 1.0);
         let _result = std::panic::catch_unwind(|| {
             assert_image("tests/multiple-frames.png", &expected_image, 1.0);
         });
-        std::env::set_var("TWENTY_TWENTY", "");
-        assert_image("artifacts/tests/multiple-frames.png", &expected_image, 1.0);
+        crate::flush_reports().unwrap();
+        std::env::remove_var("TWENTY_TWENTY_JUNIT");
+
+        let report = std::fs::read_to_string("tests/tmp/report.xml").unwrap();
+        assert!(report.contains(r#"<testcase classname="twenty-twenty" name="tests/initial-grid.png" time=""#));
+        assert!(report.contains(r#"<testcase classname="twenty-twenty" name="tests/multiple-frames.png" time=""#));
+        assert!(report.contains("less than min_permissible_similarity"));
     }
 }
@@ -0,0 +1,154 @@
+//! Review the images stored under `artifacts/` by the store-artifact modes, and the images left
+//! pending next to their references by the pending mode, and accept them as the new references,
+//! one by one.
+
+use std::{
+    io::{BufRead, Write},
+    path::{Path, PathBuf},
+};
+
+fn main() -> anyhow::Result<()> {
+    let mut artifacts = Vec::new();
+    collect_files(Path::new("artifacts"), &mut artifacts)?;
+    // Flicker GIFs, thumbnails, and environment records are there to look at alongside their
+    // artifact, not to accept, and images left pending for references fetched over HTTP have to
+    // be uploaded rather than copied into place.
+    artifacts.retain(|artifact| {
+        let artifact = artifact.to_string_lossy();
+        ![".flicker.gif", ".thumb.png", ".meta.json", ".new", ".new.json"]
+            .iter()
+            .any(|suffix| artifact.ends_with(suffix))
+    });
+    artifacts.sort();
+    let mut candidates: Vec<Candidate> = artifacts
+        .into_iter()
+        .filter_map(|artifact| {
+            let reference = twenty_twenty::reference_path(&artifact)?;
+            Some(Candidate {
+                image: artifact,
+                reference,
+                pending: false,
+            })
+        })
+        .collect();
+
+    let mut pending = Vec::new();
+    collect_pending(Path::new("."), &mut pending)?;
+    pending.sort();
+    candidates.extend(pending.into_iter().filter_map(|image| {
+        let reference = image.to_str()?.strip_suffix(".new")?.into();
+        Some(Candidate {
+            image,
+            reference,
+            pending: true,
+        })
+    }));
+
+    if candidates.is_empty() {
+        println!("no artifacts to review");
+        return Ok(());
+    }
+
+    let stdin = std::io::stdin();
+    let mut lines = stdin.lock().lines();
+    let mut accepted = 0;
+    for Candidate {
+        image,
+        reference,
+        pending,
+    } in &candidates
+    {
+        match score(image, reference) {
+            Ok(Some(score)) => println!("{} -> {} (score {})", image.display(), reference.display(), score),
+            Ok(None) => println!("{} -> {} (new reference)", image.display(), reference.display()),
+            Err(e) => {
+                println!("{}: skipping, {}", image.display(), e);
+                continue;
+            }
+        }
+
+        print!("accept? [y/N/q] ");
+        std::io::stdout().flush()?;
+        let Some(answer) = lines.next().transpose()? else {
+            break;
+        };
+        match answer.trim() {
+            "y" | "Y" => {
+                if let Some(parent) = reference.parent() {
+                    std::fs::create_dir_all(parent)?;
+                }
+                if *pending {
+                    std::fs::rename(image, reference)?;
+                    let _ = std::fs::remove_file(image.with_extension("new.json"));
+                } else {
+                    std::fs::copy(image, reference)?;
+                }
+                twenty_twenty::update_checksum(reference)?;
+                accepted += 1;
+            }
+            "q" | "Q" => break,
+            _ => {}
+        }
+    }
+
+    println!("accepted {} of {} artifacts", accepted, candidates.len());
+    Ok(())
+}
+
+/// An image that could be accepted as a new reference.
+struct Candidate {
+    /// The image to accept.
+    image: PathBuf,
+    /// The reference it would replace.
+    reference: PathBuf,
+    /// Whether it was left pending next to the reference, rather than stored as an artifact, so
+    /// it is moved rather than copied when accepted.
+    pending: bool,
+}
+
+/// Recursively collect every image left pending under `dir`, skipping `target/`, `artifacts/`,
+/// and hidden directories.
+fn collect_pending(dir: &Path, files: &mut Vec<PathBuf>) -> anyhow::Result<()> {
+    for entry in std::fs::read_dir(dir).map_err(|e| anyhow::anyhow!("unable to read {}: {}", dir.display(), e))? {
+        let path = entry?.path();
+        let name = path.file_name().unwrap_or_default().to_string_lossy();
+        if path.is_dir() {
+            if !name.starts_with('.') && name != "target" && name != "artifacts" {
+                collect_pending(&path, files)?;
+            }
+        } else if name.ends_with(".new") {
+            files.push(path);
+        }
+    }
+    Ok(())
+}
+
+/// Recursively collect every file under `dir`.
+fn collect_files(dir: &Path, files: &mut Vec<PathBuf>) -> anyhow::Result<()> {
+    let entries = match std::fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(()),
+        Err(e) => anyhow::bail!("unable to read {}: {}", dir.display(), e),
+    };
+    for entry in entries {
+        let path = entry?.path();
+        if path.is_dir() {
+            collect_files(&path, files)?;
+        } else {
+            files.push(path);
+        }
+    }
+    Ok(())
+}
+
+/// The score of the artifact against its reference, or `None` if there is no reference yet.
+fn score(artifact: &Path, reference: &Path) -> anyhow::Result<Option<f64>> {
+    if !reference.exists() {
+        return Ok(None);
+    }
+    // Pending images are named `.new`, and raw references and their artifacts are no format the
+    // extension gives away, so go by their contents.
+    let actual = twenty_twenty::read_saved_image(artifact)?;
+    let expected = twenty_twenty::read_saved_image(reference)?;
+    Ok(Some(twenty_twenty::compare_images(&expected, &actual)?))
+}
@@ -0,0 +1,81 @@
+//! Running comparisons listed in a manifest file rather than in code.
+
+use std::path::{Path, PathBuf};
+
+/// A comparison listed in a manifest.
+#[derive(serde::Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Entry {
+    /// The path of the reference image.
+    reference: PathBuf,
+    /// The path of the image to compare to the reference.
+    actual: PathBuf,
+    /// The lowest score the comparison passes with.
+    min: f64,
+    /// The metric to score the images with, rather than SSIM.
+    #[serde(default)]
+    metric: Option<crate::Metric>,
+    /// The path of a grayscale importance map, see [`crate::Config::importance_map`].
+    #[serde(default)]
+    mask: Option<PathBuf>,
+}
+
+/// Run every comparison listed in the JSON manifest at `path`, and fail listing every
+/// comparison that failed.
+/// The manifest is an array of entries like
+/// `{ "reference": "foo.png", "actual": "out/foo.png", "min": 0.99 }`, where each entry can also
+/// give a `metric` to score it with, such as `"psnr"` or `"ms-ssim"`, and a grayscale `mask` image
+/// to use as its importance map.
+/// Paths are relative to the directory holding the manifest.
+#[track_caller]
+pub fn assert_from_manifest<P: AsRef<Path>>(path: P) {
+    if let Err(e) = assert_from_manifest_impl(path.as_ref()) {
+        panic!("assertion failed: {e}")
+    }
+}
+
+fn assert_from_manifest_impl(path: &Path) -> anyhow::Result<()> {
+    let contents = std::fs::read_to_string(path)
+        .map_err(|e| anyhow::anyhow!("unable to read manifest {}: {}", path.display(), e))?;
+    let entries: Vec<Entry> = serde_json::from_str(&contents)
+        .map_err(|e| anyhow::anyhow!("unable to parse manifest {}: {}", path.display(), e))?;
+    let dir = path.parent().unwrap_or(Path::new(""));
+
+    let mut failures = Vec::new();
+    for entry in &entries {
+        if let Err(e) = check(dir, entry) {
+            failures.push(e.to_string());
+        }
+    }
+    if !failures.is_empty() {
+        anyhow::bail!(
+            "{} of {} comparisons in {} failed:\n{}",
+            failures.len(),
+            entries.len(),
+            path.display(),
+            failures.join("\n")
+        );
+    }
+
+    Ok(())
+}
+
+/// Run the comparison the entry describes.
+fn check(dir: &Path, entry: &Entry) -> anyhow::Result<()> {
+    let actual_path = dir.join(&entry.actual);
+    let actual = image::open(&actual_path)
+        .map_err(|e| anyhow::anyhow!("unable to read image {}: {}", actual_path.display(), e))?;
+
+    let mut config = crate::Config::new();
+    if let Some(metric) = entry.metric {
+        config = config.metric(metric);
+    }
+    if let Some(mask) = &entry.mask {
+        let mask_path = dir.join(mask);
+        let mask = image::open(&mask_path)
+            .map_err(|e| anyhow::anyhow!("unable to read mask {}: {}", mask_path.display(), e))?;
+        config = config.importance_map(mask.to_luma8());
+    }
+
+    crate::assert_image_impl(dir.join(&entry.reference), &actual, entry.min, &config)
+}
@@ -0,0 +1,476 @@
+//! The metrics images can be compared with.
+
+/// How the similarity of two images is scored. Every metric scores more similar images higher,
+/// but the scales differ, so a threshold tuned for one metric doesn't carry over to another.
+/// In a manifest, see [`crate::assert_from_manifest`], metrics are named in kebab-case, e.g.
+/// `"ms-ssim"`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, serde::Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Metric {
+    /// The structural similarity index (SSIM), a float between 0 and 1.
+    /// Each pixel scores the SSIM of the 8x8 window it falls in, taking the least similar of the
+    /// color channels, or the similarity of its alpha if that is lower, and the score is their
+    /// mean.
+    #[default]
+    Ssim,
+    /// The peak signal-to-noise ratio (PSNR), in decibels.
+    Psnr,
+    /// Multi-scale SSIM (MS-SSIM), which compares the contrast and structure of the images at
+    /// successive 2x downsamples and their luminance only at the coarsest, combining the scales
+    /// with the standard per-scale weights. It better matches human perception for
+    /// high-resolution images than single-scale SSIM.
+    /// The score is a float between 0 and 1, but it isn't directly comparable to a single-scale
+    /// SSIM score, so thresholds need adjusting when switching.
+    MsSsim,
+    /// The similarity of the per-channel color histograms, a float between 0 and 1.
+    /// This ignores spatial structure entirely, so it only checks that the images have the same
+    /// distribution of colors, tolerating any rearrangement of the pixels. The images don't need
+    /// to be the same size.
+    Histogram,
+    /// One minus the root-mean-square difference of the channels, normalized to `[0, 1]`, so a
+    /// float between 0 and 1 like SSIM. To give a maximum RMS error rather than a minimum score,
+    /// see [`crate::assert_image_rms`], and failures report the RMS error itself.
+    Rms,
+}
+
+/// The similarity of each pixel of two images, where 1 is identical.
+pub type SimilarityMap = image::ImageBuffer<image::Luma<f32>, Vec<f32>>;
+
+impl Metric {
+    /// Score `actual` against `expected`, which are in `color_space`, where higher scores are
+    /// more similar, resampling the images with `filter` if the metric needs to.
+    pub(crate) fn score(
+        self,
+        expected: &image::RgbaImage,
+        actual: &image::RgbaImage,
+        color_space: crate::ColorSpace,
+        filter: image::imageops::FilterType,
+    ) -> anyhow::Result<f64> {
+        match self {
+            Metric::Ssim if color_space == crate::ColorSpace::Lab => {
+                crate::color_space::lab_similarity_map(expected, actual).map(|map| mean_similarity(&map))
+            }
+            Metric::Ssim => ssim(expected, actual),
+            Metric::Psnr => psnr(expected, actual),
+            Metric::MsSsim => ms_ssim(expected, actual, filter),
+            Metric::Histogram => Ok(histogram(expected, actual)),
+            Metric::Rms => rms(expected, actual).map(|rms| 1.0 - rms),
+        }
+    }
+
+    /// Score `actual` against `expected` like [`Metric::score`], but with each pixel counting in
+    /// proportion to its weight, from an image of the same size.
+    pub(crate) fn weighted_score(
+        self,
+        expected: &image::RgbaImage,
+        actual: &image::RgbaImage,
+        color_space: crate::ColorSpace,
+        weights: &image::GrayImage,
+    ) -> anyhow::Result<f64> {
+        match self {
+            Metric::Ssim => {
+                let map = similarity_map(expected, actual, color_space)?;
+                Ok(weighted_mean(map.pixels().map(|p| p[0] as f64), weights).unwrap_or(1.0))
+            }
+            Metric::Psnr => {
+                let mse = weighted_mean_squared_error(expected, actual, weights)?;
+                Ok(if mse == 0.0 { f64::INFINITY } else { -10.0 * mse.log10() })
+            }
+            Metric::Rms => weighted_mean_squared_error(expected, actual, weights).map(|mse| 1.0 - mse.sqrt()),
+            Metric::MsSsim => anyhow::bail!("MS-SSIM scores can't be weighted by an importance map"),
+            Metric::Histogram => anyhow::bail!("histogram scores can't be weighted by an importance map"),
+        }
+    }
+
+    /// The score of identical images.
+    pub(crate) fn identical_score(self) -> f64 {
+        match self {
+            Metric::Ssim | Metric::MsSsim | Metric::Histogram | Metric::Rms => 1.0,
+            Metric::Psnr => f64::INFINITY,
+        }
+    }
+
+    /// Describe a score that fell below the minimum.
+    pub(crate) fn describe_mismatch(self, score: f64, min: f64) -> String {
+        match self {
+            Metric::Ssim | Metric::MsSsim | Metric::Histogram => {
+                format!("score is `{score}` which is less than min_permissible_similarity `{min}`")
+            }
+            Metric::Psnr => format!("PSNR is `{score} dB` which is less than the minimum of `{min} dB`"),
+            Metric::Rms => format!(
+                "RMS error is `{}` which is more than the maximum of `{}`",
+                1.0 - score,
+                1.0 - min
+            ),
+        }
+    }
+
+    /// Describe a score that rose above the maximum.
+    pub(crate) fn describe_too_similar(self, score: f64, max: f64) -> String {
+        match self {
+            Metric::Ssim | Metric::MsSsim | Metric::Histogram => {
+                format!("score is `{score}` which is more than max_similarity `{max}`")
+            }
+            Metric::Psnr => format!("PSNR is `{score} dB` which is more than the maximum of `{max} dB`"),
+            Metric::Rms => format!(
+                "RMS error is `{}` which is less than the minimum of `{}`",
+                1.0 - score,
+                1.0 - max
+            ),
+        }
+    }
+}
+
+/// The similarity of each pixel of the images, which are in `color_space`, where 1 is identical.
+pub(crate) fn similarity_map(
+    expected: &image::RgbaImage,
+    actual: &image::RgbaImage,
+    color_space: crate::ColorSpace,
+) -> anyhow::Result<SimilarityMap> {
+    match color_space {
+        crate::ColorSpace::Srgb => ssim_map(expected, actual).map(|(_, map)| map),
+        crate::ColorSpace::Lab => crate::color_space::lab_similarity_map(expected, actual),
+    }
+}
+
+/// The SSIM score of `actual` against `expected`, the mean of their [`ssim_map`].
+pub(crate) fn ssim(expected: &image::RgbaImage, actual: &image::RgbaImage) -> anyhow::Result<f64> {
+    ssim_map(expected, actual).map(|(score, _)| score)
+}
+
+/// The similarity of each pixel of `actual` against `expected`, where 1 is identical, along with
+/// their mean.
+/// Each pixel scores the SSIM of the window it is in, computed like [`windowed_ssim`] and taking
+/// the least similar of the color channels, or the similarity of its alpha if that is lower.
+/// Differences count less as the pixels get more transparent.
+pub(crate) fn ssim_map(expected: &image::RgbaImage, actual: &image::RgbaImage) -> anyhow::Result<(f64, SimilarityMap)> {
+    ensure_same_size(expected.dimensions(), actual.dimensions())?;
+    let (width, height) = expected.dimensions();
+    let expected_channel = |x, y, c: usize| expected.get_pixel(x, y)[c] as f64 / 255.0;
+    let actual_channel = |x, y, c: usize| actual.get_pixel(x, y)[c] as f64 / 255.0;
+    let structure = windowed_map(width, height, |x0, y0| {
+        (0..3)
+            .map(|c| window_ssim(width, height, x0, y0, c, &expected_channel, &actual_channel))
+            .fold(f64::INFINITY, f64::min)
+    });
+
+    let map = SimilarityMap::from_fn(width, height, |x, y| {
+        image::Luma([pixel_similarity(
+            structure.get_pixel(x, y)[0],
+            expected.get_pixel(x, y)[3],
+            actual.get_pixel(x, y)[3],
+        )])
+    });
+    Ok((mean_similarity(&map), map))
+}
+
+/// The mean similarity of the pixels of a map, summed the same way [`weighted_mean`] sums them,
+/// so a uniform importance map scores exactly the same.
+fn mean_similarity(map: &SimilarityMap) -> f64 {
+    let total = map.pixels().fold(0.0, |total, p| total + p[0] as f64);
+    total / map.pixels().len().max(1) as f64
+}
+
+/// The similarity of a pixel from the SSIM of its window: the lower of that and the similarity
+/// of its alpha, discounted where the images are translucent.
+fn pixel_similarity(structure: f32, expected_alpha: u8, actual_alpha: u8) -> f32 {
+    let alpha = 1.0 - (expected_alpha as f32 - actual_alpha as f32).abs() / 255.0;
+    let mean_alpha = (expected_alpha as f32 + actual_alpha as f32) / (2.0 * 255.0);
+    if mean_alpha > 0.0 {
+        (structure.min(alpha) / mean_alpha).clamp(0.0, 1.0)
+    } else {
+        1.0
+    }
+}
+
+/// The standard weights of each scale of MS-SSIM, from full resolution down.
+const MS_SSIM_WEIGHTS: [f64; 5] = [0.0448, 0.2856, 0.3001, 0.2363, 0.1333];
+
+/// The MS-SSIM score of `actual` against `expected`: the product of the mean contrast-structure
+/// term of SSIM at each scale but the coarsest, and the mean SSIM at the coarsest, each raised to
+/// the weight of its scale.
+/// Each scale is downsampled from the last with `filter`. Scales that would be too small to hold
+/// an SSIM window are skipped, and the weights of the remaining scales renormalized.
+pub(crate) fn ms_ssim(
+    expected: &image::RgbaImage,
+    actual: &image::RgbaImage,
+    filter: image::imageops::FilterType,
+) -> anyhow::Result<f64> {
+    ensure_same_size(expected.dimensions(), actual.dimensions())?;
+
+    let mut expected = expected.clone();
+    let mut actual = actual.clone();
+    let mut scales = Vec::with_capacity(MS_SSIM_WEIGHTS.len());
+    for scale in 0..MS_SSIM_WEIGHTS.len() {
+        if scale > 0 {
+            if expected.width() < 32 || expected.height() < 32 {
+                break;
+            }
+            let (width, height) = (expected.width() / 2, expected.height() / 2);
+            expected = image::imageops::resize(&expected, width, height, filter);
+            actual = image::imageops::resize(&actual, width, height, filter);
+        }
+        scales.push(windowed_ssim_terms(
+            expected.width(),
+            expected.height(),
+            |x, y, c| expected.get_pixel(x, y)[c] as f64 / 255.0,
+            |x, y, c| actual.get_pixel(x, y)[c] as f64 / 255.0,
+        ));
+    }
+
+    let coarsest = scales.len() - 1;
+    let total_weight: f64 = MS_SSIM_WEIGHTS.iter().take(scales.len()).sum();
+    Ok(scales
+        .iter()
+        .zip(MS_SSIM_WEIGHTS)
+        .enumerate()
+        .map(|(scale, (&(contrast_structure, ssim), weight))| {
+            let term = if scale == coarsest { ssim } else { contrast_structure };
+            term.max(0.0).powf(weight / total_weight)
+        })
+        .product())
+}
+
+/// The similarity of the per-channel histograms of the images, averaged over the channels.
+/// Each channel scores the Bhattacharyya coefficient of its normalized histograms, which is 1
+/// for identical distributions and 0 for distributions with no values in common.
+pub(crate) fn histogram(expected: &image::RgbaImage, actual: &image::RgbaImage) -> f64 {
+    let expected = channel_histograms(expected);
+    let actual = channel_histograms(actual);
+    let total: f64 = expected
+        .iter()
+        .zip(&actual)
+        .map(|(expected, actual)| expected.iter().zip(actual).map(|(e, a)| (e * a).sqrt()).sum::<f64>())
+        .sum();
+    total / expected.len() as f64
+}
+
+/// The normalized histogram of each channel of the image.
+fn channel_histograms(image: &image::RgbaImage) -> [[f64; 256]; 4] {
+    let mut histograms = [[0.0; 256]; 4];
+    for pixel in image.pixels() {
+        for (histogram, &value) in histograms.iter_mut().zip(&pixel.0) {
+            histogram[value as usize] += 1.0;
+        }
+    }
+    let pixel_count = (image.width() as f64 * image.height() as f64).max(1.0);
+    for bin in histograms.iter_mut().flatten() {
+        *bin /= pixel_count;
+    }
+    histograms
+}
+
+/// Fail unless two images, by their dimensions, are the same size, which every metric but the
+/// histogram needs to line their pixels up.
+pub(crate) fn ensure_same_size(expected: (u32, u32), actual: (u32, u32)) -> anyhow::Result<()> {
+    if expected != actual {
+        anyhow::bail!(
+            "could not compare the images, expected is {}x{} but actual is {}x{}",
+            expected.0,
+            expected.1,
+            actual.0,
+            actual.1
+        );
+    }
+    Ok(())
+}
+
+/// The peak signal-to-noise ratio of `actual` against `expected` over every channel, in
+/// decibels. Identical images have an infinite PSNR.
+pub(crate) fn psnr(expected: &image::RgbaImage, actual: &image::RgbaImage) -> anyhow::Result<f64> {
+    ensure_same_size(expected.dimensions(), actual.dimensions())?;
+
+    let squared_error: f64 = expected
+        .as_raw()
+        .iter()
+        .zip(actual.as_raw())
+        .map(|(&e, &a)| (e as f64 - a as f64).powi(2))
+        .sum();
+    let mse = squared_error / expected.as_raw().len().max(1) as f64;
+    if mse == 0.0 {
+        return Ok(f64::INFINITY);
+    }
+
+    Ok(10.0 * (255.0 * 255.0 / mse).log10())
+}
+
+/// The root-mean-square difference of the channels of the images, normalized to `[0, 1]`.
+pub(crate) fn rms(expected: &image::RgbaImage, actual: &image::RgbaImage) -> anyhow::Result<f64> {
+    ensure_same_size(expected.dimensions(), actual.dimensions())?;
+
+    let squared_error: f64 = expected
+        .as_raw()
+        .iter()
+        .zip(actual.as_raw())
+        .map(|(&e, &a)| ((e as f64 - a as f64) / 255.0).powi(2))
+        .sum();
+    Ok((squared_error / expected.as_raw().len().max(1) as f64).sqrt())
+}
+
+/// The mean squared difference of the channels of each pixel of the images, normalized to
+/// `[0, 1]`, with each pixel counting in proportion to its weight.
+fn weighted_mean_squared_error(
+    expected: &image::RgbaImage,
+    actual: &image::RgbaImage,
+    weights: &image::GrayImage,
+) -> anyhow::Result<f64> {
+    ensure_same_size(expected.dimensions(), actual.dimensions())?;
+
+    let squared_errors = expected.pixels().zip(actual.pixels()).map(|(e, a)| {
+        e.0.iter()
+            .zip(a.0)
+            .map(|(&e, a)| ((e as f64 - a as f64) / 255.0).powi(2))
+            .sum::<f64>()
+            / 4.0
+    });
+    Ok(weighted_mean(squared_errors, weights).unwrap_or(0.0))
+}
+
+/// The mean of the values of each pixel, weighted by the pixel of `weights`, or `None` if every
+/// weight is 0.
+/// Weights are scaled to `[0, 1]` first, so a map that is white everywhere gives exactly the
+/// unweighted mean.
+fn weighted_mean(values: impl Iterator<Item = f64>, weights: &image::GrayImage) -> Option<f64> {
+    let (total, total_weight) =
+        values
+            .zip(weights.pixels())
+            .fold((0.0, 0.0), |(total, total_weight), (value, weight)| {
+                let weight = weight[0] as f64 / 255.0;
+                (total + value * weight, total_weight + weight)
+            });
+    (total_weight > 0.0).then(|| total / total_weight)
+}
+
+/// The side of the thumbnails [`coarse_similarity`] compares.
+const COARSE_SIZE: u32 = 16;
+
+/// A cheap estimate of how similar the images are: one minus the mean difference in color between
+/// small thumbnails of them, on a scale of 0 to 1. It only tells grossly different images apart,
+/// so it is `None` for images of different sizes, for the metric to reject.
+pub(crate) fn coarse_similarity(expected: &image::RgbaImage, actual: &image::RgbaImage) -> Option<f64> {
+    if expected.dimensions() != actual.dimensions() {
+        return None;
+    }
+    let thumbnail = |image: &image::RgbaImage| {
+        image::imageops::resize(image, COARSE_SIZE, COARSE_SIZE, image::imageops::FilterType::Triangle)
+    };
+    let (expected, actual) = (thumbnail(expected), thumbnail(actual));
+    let difference: f64 = expected
+        .pixels()
+        .zip(actual.pixels())
+        .flat_map(|(e, a)| (0..3).map(move |channel| e[channel].abs_diff(a[channel]) as f64 / 255.0))
+        .sum();
+    Some(1.0 - difference / (expected.pixels().len() * 3).max(1) as f64)
+}
+
+/// The side of the square windows [`windowed_ssim`] is computed over.
+const WINDOW: u32 = 8;
+/// Stabilizes the luminance term of SSIM for a dynamic range of 1.
+const C1: f64 = 0.01 * 0.01;
+/// Stabilizes the contrast term of SSIM for a dynamic range of 1.
+const C2: f64 = 0.03 * 0.03;
+
+/// The mean SSIM of the color channels of two `width` by `height` images, over non-overlapping
+/// windows, for images that aren't 8-bit. `expected` and `actual` give the value of a channel of
+/// a pixel, normalized to `[0, 1]`.
+pub(crate) fn windowed_ssim(
+    width: u32,
+    height: u32,
+    expected: impl Fn(u32, u32, usize) -> f64,
+    actual: impl Fn(u32, u32, usize) -> f64,
+) -> f64 {
+    windowed_ssim_terms(width, height, expected, actual).1
+}
+
+/// The mean contrast-structure term of SSIM and the mean SSIM of the color channels of two
+/// images, over non-overlapping windows, with the arguments of [`windowed_ssim`].
+fn windowed_ssim_terms(
+    width: u32,
+    height: u32,
+    expected: impl Fn(u32, u32, usize) -> f64,
+    actual: impl Fn(u32, u32, usize) -> f64,
+) -> (f64, f64) {
+    let (mut contrast_structure, mut ssim) = (0.0, 0.0);
+    let mut windows = 0;
+    for y0 in (0..height).step_by(WINDOW as usize) {
+        for x0 in (0..width).step_by(WINDOW as usize) {
+            for channel in 0..3 {
+                let (luminance, window_contrast_structure) =
+                    window_terms(width, height, x0, y0, channel, &expected, &actual);
+                contrast_structure += window_contrast_structure;
+                ssim += luminance * window_contrast_structure;
+                windows += 1;
+            }
+        }
+    }
+
+    let windows = windows.max(1) as f64;
+    (contrast_structure / windows, ssim / windows)
+}
+
+/// A `width` by `height` map where every pixel of each window holds the similarity of that
+/// window, given the top-left corner of the window.
+pub(crate) fn windowed_map(width: u32, height: u32, similarity: impl Fn(u32, u32) -> f64) -> SimilarityMap {
+    let mut map = SimilarityMap::new(width, height);
+    for y0 in (0..height).step_by(WINDOW as usize) {
+        for x0 in (0..width).step_by(WINDOW as usize) {
+            let similarity = similarity(x0, y0) as f32;
+            for y in y0..(y0 + WINDOW).min(height) {
+                for x in x0..(x0 + WINDOW).min(width) {
+                    map.put_pixel(x, y, image::Luma([similarity]));
+                }
+            }
+        }
+    }
+    map
+}
+
+/// The SSIM of one channel of two `width` by `height` images over the window with its top-left
+/// corner at `(x0, y0)`, with `expected` and `actual` like [`windowed_ssim`].
+pub(crate) fn window_ssim(
+    width: u32,
+    height: u32,
+    x0: u32,
+    y0: u32,
+    channel: usize,
+    expected: &impl Fn(u32, u32, usize) -> f64,
+    actual: &impl Fn(u32, u32, usize) -> f64,
+) -> f64 {
+    let (luminance, contrast_structure) = window_terms(width, height, x0, y0, channel, expected, actual);
+    luminance * contrast_structure
+}
+
+/// The luminance and contrast-structure terms of the SSIM of one channel over a window, whose
+/// product is the SSIM, with the arguments of [`window_ssim`].
+fn window_terms(
+    width: u32,
+    height: u32,
+    x0: u32,
+    y0: u32,
+    channel: usize,
+    expected: &impl Fn(u32, u32, usize) -> f64,
+    actual: &impl Fn(u32, u32, usize) -> f64,
+) -> (f64, f64) {
+    let (mut sum_e, mut sum_a, mut sum_ee, mut sum_aa, mut sum_ea) = (0.0, 0.0, 0.0, 0.0, 0.0);
+    let mut n = 0.0;
+    for y in y0..(y0 + WINDOW).min(height) {
+        for x in x0..(x0 + WINDOW).min(width) {
+            let e = expected(x, y, channel);
+            let a = actual(x, y, channel);
+            sum_e += e;
+            sum_a += a;
+            sum_ee += e * e;
+            sum_aa += a * a;
+            sum_ea += e * a;
+            n += 1.0;
+        }
+    }
+    let (mean_e, mean_a) = (sum_e / n, sum_a / n);
+    let variance_e = sum_ee / n - mean_e * mean_e;
+    let variance_a = sum_aa / n - mean_a * mean_a;
+    let covariance = sum_ea / n - mean_e * mean_a;
+    (
+        (2.0 * mean_e * mean_a + C1) / (mean_e * mean_e + mean_a * mean_a + C1),
+        (2.0 * covariance + C2) / (variance_e + variance_a + C2),
+    )
+}
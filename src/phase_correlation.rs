@@ -0,0 +1,188 @@
+//! Finding the translation between two images by phase correlation.
+
+use std::f64::consts::PI;
+
+use image::Pixel;
+
+/// The smallest share of the images that has to overlap at a candidate shift, so a large shift
+/// can't win by lining up a sliver along the edge.
+const MIN_OVERLAP: f64 = 0.5;
+
+/// Find the offset into `actual` that lines it up with `expected`, within `max_shift` pixels in
+/// each direction and overlapping at least [`MIN_OVERLAP`] of the images, from the peak of the
+/// phase correlation of their luma. Ties go to the smallest shift, so identical images are never
+/// shifted. The images must be the same size.
+pub(crate) fn find_shift(expected: &image::RgbaImage, actual: &image::RgbaImage, max_shift: u32) -> (i64, i64) {
+    let (width, height) = actual.dimensions();
+    let (size_x, size_y) = (
+        (width as usize).next_power_of_two(),
+        (height as usize).next_power_of_two(),
+    );
+
+    // The normalized cross-power spectrum keeps only the phase difference between the images,
+    // which transforms back into a peak at the offset between them.
+    let mut correlation = spectrum(actual, size_x, size_y);
+    for (value, expected) in correlation.iter_mut().zip(spectrum(expected, size_x, size_y)) {
+        let product = *value * expected.conj();
+        let magnitude = product.norm();
+        *value = if magnitude > f64::EPSILON {
+            product.scale(1.0 / magnitude)
+        } else {
+            Complex::default()
+        };
+    }
+    fft_2d(&mut correlation, size_x, size_y, true);
+
+    let max_shift = max_shift as i64;
+    let (width, height) = (width as i64, height as i64);
+    let mut shifts = Vec::new();
+    for dy in -max_shift..=max_shift {
+        for dx in -max_shift..=max_shift {
+            let overlap = (width - dx.abs()).max(0) * (height - dy.abs()).max(0);
+            if overlap as f64 >= MIN_OVERLAP * (width * height) as f64 {
+                shifts.push((dx, dy));
+            }
+        }
+    }
+    shifts.sort_by_key(|(dx, dy)| dx.abs() + dy.abs());
+
+    let mut best = ((0, 0), f64::NEG_INFINITY);
+    for (dx, dy) in shifts {
+        let x = dx.rem_euclid(size_x as i64) as usize;
+        let y = dy.rem_euclid(size_y as i64) as usize;
+        let peak = correlation[y * size_x + x].re;
+        if peak > best.1 {
+            best = ((dx, dy), peak);
+        }
+    }
+    best.0
+}
+
+/// The 2D Fourier transform of the luma of the image, less its mean so the flat padding up to
+/// `size_x` by `size_y` doesn't add an edge of its own.
+fn spectrum(image: &image::RgbaImage, size_x: usize, size_y: usize) -> Vec<Complex> {
+    let luma = image.pixels().map(|p| p.to_luma()[0] as f64);
+    let mean = luma.clone().sum::<f64>() / (image.width() * image.height()).max(1) as f64;
+
+    let mut values = vec![Complex::default(); size_x * size_y];
+    for ((x, y, _), luma) in image.enumerate_pixels().zip(luma) {
+        values[y as usize * size_x + x as usize].re = luma - mean;
+    }
+    fft_2d(&mut values, size_x, size_y, false);
+    values
+}
+
+/// Transform the `size_x` by `size_y` grid of values in place, row by row and then column by
+/// column. Both sizes must be powers of two. The inverse transform isn't scaled, which doesn't
+/// move the peak.
+fn fft_2d(values: &mut [Complex], size_x: usize, size_y: usize, inverse: bool) {
+    for row in values.chunks_mut(size_x) {
+        fft(row, inverse);
+    }
+    let mut column = vec![Complex::default(); size_y];
+    for x in 0..size_x {
+        for (y, value) in column.iter_mut().enumerate() {
+            *value = values[y * size_x + x];
+        }
+        fft(&mut column, inverse);
+        for (y, value) in column.iter().enumerate() {
+            values[y * size_x + x] = *value;
+        }
+    }
+}
+
+/// The iterative radix-2 Cooley-Tukey transform, in place. The length must be a power of two.
+fn fft(values: &mut [Complex], inverse: bool) {
+    let n = values.len();
+
+    let mut j = 0;
+    for i in 1..n {
+        let mut bit = n >> 1;
+        while j & bit != 0 {
+            j ^= bit;
+            bit >>= 1;
+        }
+        j |= bit;
+        if i < j {
+            values.swap(i, j);
+        }
+    }
+
+    let mut len = 2;
+    while len <= n {
+        let angle = if inverse { 2.0 } else { -2.0 } * PI / len as f64;
+        let step = Complex {
+            re: angle.cos(),
+            im: angle.sin(),
+        };
+        for start in (0..n).step_by(len) {
+            let mut twiddle = Complex { re: 1.0, im: 0.0 };
+            for k in start..start + len / 2 {
+                let (even, odd) = (values[k], values[k + len / 2] * twiddle);
+                values[k] = even + odd;
+                values[k + len / 2] = even - odd;
+                twiddle = twiddle * step;
+            }
+        }
+        len <<= 1;
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default)]
+struct Complex {
+    re: f64,
+    im: f64,
+}
+
+impl Complex {
+    fn conj(self) -> Self {
+        Complex {
+            re: self.re,
+            im: -self.im,
+        }
+    }
+
+    fn norm(self) -> f64 {
+        self.re.hypot(self.im)
+    }
+
+    fn scale(self, factor: f64) -> Self {
+        Complex {
+            re: self.re * factor,
+            im: self.im * factor,
+        }
+    }
+}
+
+impl std::ops::Add for Complex {
+    type Output = Complex;
+
+    fn add(self, other: Complex) -> Complex {
+        Complex {
+            re: self.re + other.re,
+            im: self.im + other.im,
+        }
+    }
+}
+
+impl std::ops::Sub for Complex {
+    type Output = Complex;
+
+    fn sub(self, other: Complex) -> Complex {
+        Complex {
+            re: self.re - other.re,
+            im: self.im - other.im,
+        }
+    }
+}
+
+impl std::ops::Mul for Complex {
+    type Output = Complex;
+
+    fn mul(self, other: Complex) -> Complex {
+        Complex {
+            re: self.re * other.re - self.im * other.im,
+            im: self.re * other.im + self.im * other.re,
+        }
+    }
+}
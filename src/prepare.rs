@@ -0,0 +1,486 @@
+//! The transformations applied to images before they are compared.
+
+use std::borrow::Cow;
+
+use crate::Config;
+
+/// Both images as they are compared, along with how much each of their pixels counts towards
+/// the score if there is an importance map.
+pub(crate) struct Prepared<'a> {
+    pub(crate) expected: Cow<'a, image::RgbaImage>,
+    pub(crate) actual: Cow<'a, image::RgbaImage>,
+    pub(crate) weights: Option<image::GrayImage>,
+    /// The offset into the actual image [`Config::auto_align`] shifted it by.
+    pub(crate) shift: (i64, i64),
+    /// Where the top left pixel of the prepared images is in the actual image as it was given, or
+    /// `None` if the actual image was resampled so its pixels can't be mapped back.
+    pub(crate) origin: Option<(i64, i64)>,
+}
+
+/// Apply the configured transformations to both images before comparison, converting them to
+/// RGBA8. Images that are already RGBA8 are only copied if a transformation changes them.
+pub(crate) fn prepare<'a>(
+    config: &Config,
+    expected: &'a image::DynamicImage,
+    actual: &'a image::DynamicImage,
+) -> anyhow::Result<Prepared<'a>> {
+    let mut expected = crate::rgba8(expected);
+    let mut actual = crate::rgba8(actual);
+
+    if !config.mask_regions.is_empty() {
+        actual = Cow::Owned(mask_regions(&expected, &actual, &config.mask_regions));
+    }
+
+    let mut origin = Some((0, 0));
+    let mut offset_origin = |(x, y): (i64, i64)| origin = origin.map(|(ox, oy)| (ox + x, oy + y));
+
+    if let Some(background) = config.autocrop {
+        let (cropped, _) = autocrop(&expected, background)
+            .ok_or_else(|| anyhow::anyhow!("the expected image is entirely background"))?;
+        expected = Cow::Owned(cropped);
+        let (cropped, (x, y)) =
+            autocrop(&actual, background).ok_or_else(|| anyhow::anyhow!("the actual image is entirely background"))?;
+        actual = Cow::Owned(cropped);
+        offset_origin((x as i64, y as i64));
+    }
+
+    if config.strip_letterbox {
+        if let Some((stripped, _)) = strip_letterbox(&expected) {
+            expected = Cow::Owned(stripped);
+        }
+        if let Some((stripped, (x, y))) = strip_letterbox(&actual) {
+            actual = Cow::Owned(stripped);
+            offset_origin((x as i64, y as i64));
+        }
+        if expected.dimensions() != actual.dimensions() {
+            let (width, height) = expected.dimensions();
+            // The content of a letterboxed image is scaled down to fit, so it only has to be in
+            // proportion to the reference, to the nearest pixel.
+            let proportional_width = actual.width() as f64 * height as f64 / actual.height() as f64;
+            if (proportional_width - width as f64).abs() > 1.0 {
+                anyhow::bail!(
+                    "without its letterboxing the actual image is {}x{}, which isn't in proportion to the {}x{} \
+                     reference",
+                    actual.width(),
+                    actual.height(),
+                    width,
+                    height
+                );
+            }
+            actual = Cow::Owned(image::imageops::resize(&*actual, width, height, config.filter()));
+            origin = None;
+        }
+    }
+
+    if let Some(scale) = config.expected_scale {
+        let dimensions = actual.dimensions();
+        rescale(&mut expected, &mut actual, scale, config.filter())?;
+        if actual.dimensions() != dimensions {
+            origin = None;
+        }
+    }
+
+    let mut shift = (0, 0);
+    if let Some(max_shift) = config.auto_align {
+        let aligned;
+        (aligned, shift) = align(&expected, &actual, max_shift);
+        actual = Cow::Owned(aligned);
+        origin = origin.map(|(x, y)| (x + shift.0, y + shift.1));
+    }
+
+    if let Some(min_neighbours) = config.denoise_isolated {
+        actual = Cow::Owned(denoise_isolated(&expected, &actual, min_neighbours));
+    }
+
+    if let Some(radius) = config.edge_tolerance {
+        actual = Cow::Owned(tolerate_jitter(&expected, &actual, radius));
+    }
+
+    let mut weights = match &config.importance_map {
+        Some(importance_map) => Some(importance_weights(&expected, &actual, importance_map)?),
+        None => None,
+    };
+
+    if config.reference_alpha_as_mask {
+        actual = Cow::Owned(mask_by_reference_alpha(&expected, &actual)?);
+    }
+
+    if config.ignore_border > 0 {
+        expected = Cow::Owned(crop_border(&expected, config.ignore_border)?);
+        actual = Cow::Owned(crop_border(&actual, config.ignore_border)?);
+        origin = origin.map(|(x, y)| (x + config.ignore_border as i64, y + config.ignore_border as i64));
+        weights = weights.map(|weights| {
+            let (width, height) = expected.dimensions();
+            image::imageops::crop_imm(&weights, config.ignore_border, config.ignore_border, width, height).to_image()
+        });
+    }
+
+    for image in [&mut expected, &mut actual] {
+        if let Some(background) = config.composite_over {
+            composite_over(image.to_mut(), background);
+        }
+        if config.ignore_alpha {
+            make_opaque(image.to_mut());
+        }
+        if config.linearize && config.color_space == crate::ColorSpace::Srgb {
+            linearize(image.to_mut());
+        }
+        if config.ignore_transparent {
+            zero_transparent(image.to_mut());
+        }
+        if config.premultiply_alpha {
+            premultiply_alpha(image.to_mut());
+        }
+        if config.color_space != crate::ColorSpace::Srgb {
+            config.color_space.convert(image.to_mut());
+        }
+        if let Some(channel) = config.channel {
+            *image = Cow::Owned(channel.isolate(image));
+        }
+    }
+
+    Ok(Prepared {
+        expected,
+        actual,
+        weights,
+        shift,
+        origin,
+    })
+}
+
+/// Crop the image to the bounding box of the pixels that aren't the background color, along with
+/// the position of its top left corner, or `None` if every pixel is.
+fn autocrop(image: &image::RgbaImage, background: image::Rgba<u8>) -> Option<(image::RgbaImage, (u32, u32))> {
+    let mut bounds: Option<(u32, u32, u32, u32)> = None;
+    for (x, y, pixel) in image.enumerate_pixels() {
+        if *pixel != background {
+            let (min_x, min_y, max_x, max_y) = bounds.unwrap_or((x, y, x, y));
+            bounds = Some((min_x.min(x), min_y.min(y), max_x.max(x), max_y.max(y)));
+        }
+    }
+    let (min_x, min_y, max_x, max_y) = bounds?;
+    let cropped = image::imageops::crop_imm(image, min_x, min_y, max_x - min_x + 1, max_y - min_y + 1).to_image();
+    Some((cropped, (min_x, min_y)))
+}
+
+/// Crop off the bars of uniform color along the edges of a letterboxed or pillarboxed image,
+/// taking the same number of rows off the top and bottom and of columns off the left and right,
+/// so content that happens to run up to one edge isn't cropped with them. Returns the stripped
+/// image along with the position of its top left corner, or `None` if there are no bars, or the
+/// whole image is one color.
+fn strip_letterbox(image: &image::RgbaImage) -> Option<(image::RgbaImage, (u32, u32))> {
+    let (width, height) = image.dimensions();
+    let bar = *image.get_pixel_checked(0, 0)?;
+    let row_is_bar = |y: u32| (0..width).all(|x| *image.get_pixel(x, y) == bar);
+    let column_is_bar = |x: u32| (0..height).all(|y| *image.get_pixel(x, y) == bar);
+
+    let top = (0..height).take_while(|&y| row_is_bar(y)).count() as u32;
+    if top == height {
+        return None;
+    }
+    let bottom = (0..height).rev().take_while(|&y| row_is_bar(y)).count() as u32;
+    let left = (0..width).take_while(|&x| column_is_bar(x)).count() as u32;
+    let right = (0..width).rev().take_while(|&x| column_is_bar(x)).count() as u32;
+    let (rows, columns) = (top.min(bottom), left.min(right));
+    if rows == 0 && columns == 0 {
+        return None;
+    }
+
+    let stripped = image::imageops::crop_imm(image, columns, rows, width - 2 * columns, height - 2 * rows).to_image();
+    Some((stripped, (columns, rows)))
+}
+
+/// Resample whichever image is larger down to the size of the other, where `actual` is `scale`
+/// times the size of `expected`. Images that are already the same size, like the downscaled
+/// copies [`Config::prescreen`] compares, are left alone.
+fn rescale<'a>(
+    expected: &mut Cow<'a, image::RgbaImage>,
+    actual: &mut Cow<'a, image::RgbaImage>,
+    scale: f64,
+    filter: image::imageops::FilterType,
+) -> anyhow::Result<()> {
+    anyhow::ensure!(
+        scale.is_finite() && scale > 0.0,
+        "the expected scale must be positive, not {scale}"
+    );
+    let (expected_dimensions, actual_dimensions) = (expected.dimensions(), actual.dimensions());
+    if expected_dimensions == actual_dimensions {
+        return Ok(());
+    }
+
+    let scaled = |(width, height): (u32, u32), factor: f64| {
+        (
+            (width as f64 * factor).round() as u32,
+            (height as f64 * factor).round() as u32,
+        )
+    };
+    let (larger, smaller, factor) = if scale >= 1.0 {
+        (actual, &**expected, 1.0 / scale)
+    } else {
+        (expected, &**actual, scale)
+    };
+    let (width, height) = scaled(larger.dimensions(), factor);
+    if (width, height) != smaller.dimensions() {
+        anyhow::bail!(
+            "the actual image is {}x{}, which doesn't match the {}x{} reference at a scale of {}",
+            actual_dimensions.0,
+            actual_dimensions.1,
+            expected_dimensions.0,
+            expected_dimensions.1,
+            scale
+        );
+    }
+    if factor != 1.0 {
+        *larger = Cow::Owned(image::imageops::resize(&**larger, width, height, filter));
+    }
+
+    Ok(())
+}
+
+/// Shift `actual` by the offset, within `max_shift` pixels in each direction, that best lines it
+/// up with `expected`, filling the strip the shift uncovers from `expected` so it can't affect the
+/// score, and return it along with the offset. Images of different sizes are left alone for the
+/// metric to reject.
+fn align(expected: &image::RgbaImage, actual: &image::RgbaImage, max_shift: u32) -> (image::RgbaImage, (i64, i64)) {
+    if expected.dimensions() != actual.dimensions() {
+        return (actual.clone(), (0, 0));
+    }
+
+    let (dx, dy) = crate::phase_correlation::find_shift(expected, actual, max_shift);
+    let (width, height) = actual.dimensions();
+    let aligned = image::RgbaImage::from_fn(width, height, |x, y| {
+        let (source_x, source_y) = (x as i64 + dx, y as i64 + dy);
+        if (0..width as i64).contains(&source_x) && (0..height as i64).contains(&source_y) {
+            *actual.get_pixel(source_x as u32, source_y as u32)
+        } else {
+            *expected.get_pixel(x, y)
+        }
+    });
+    (aligned, (dx, dy))
+}
+
+/// Undo the differences in `actual` at pixels with fewer than `min_neighbours` differing pixels
+/// among their eight neighbours, so stray speckles can't affect the score. Images of different
+/// sizes are left alone for the metric to reject.
+fn denoise_isolated(expected: &image::RgbaImage, actual: &image::RgbaImage, min_neighbours: u32) -> image::RgbaImage {
+    if expected.dimensions() != actual.dimensions() {
+        return actual.clone();
+    }
+
+    let (width, height) = actual.dimensions();
+    let differs = |x: u32, y: u32| expected.get_pixel(x, y) != actual.get_pixel(x, y);
+    let mut denoised = actual.clone();
+    for y in 0..height {
+        for x in 0..width {
+            if !differs(x, y) {
+                continue;
+            }
+            let mut neighbours = 0;
+            for ny in y.saturating_sub(1)..=(y + 1).min(height - 1) {
+                for nx in x.saturating_sub(1)..=(x + 1).min(width - 1) {
+                    if (nx, ny) != (x, y) && differs(nx, ny) {
+                        neighbours += 1;
+                    }
+                }
+            }
+            if neighbours < min_neighbours {
+                denoised.put_pixel(x, y, *expected.get_pixel(x, y));
+            }
+        }
+    }
+    denoised
+}
+
+/// Undo the differences in `actual` at pixels whose color appears in `expected` within `radius`
+/// pixels, in a square around them, so features that moved by up to `radius` pixels don't
+/// affect the score. Images of different sizes are left alone for the metric to reject.
+fn tolerate_jitter(expected: &image::RgbaImage, actual: &image::RgbaImage, radius: u32) -> image::RgbaImage {
+    if expected.dimensions() != actual.dimensions() {
+        return actual.clone();
+    }
+
+    let (width, height) = actual.dimensions();
+    let mut tolerated = actual.clone();
+    for (x, y, pixel) in actual.enumerate_pixels() {
+        if expected.get_pixel(x, y) == pixel {
+            continue;
+        }
+        let nearby = (y.saturating_sub(radius)..=y.saturating_add(radius).min(height - 1)).any(|ny| {
+            (x.saturating_sub(radius)..=x.saturating_add(radius).min(width - 1))
+                .any(|nx| expected.get_pixel(nx, ny) == pixel)
+        });
+        if nearby {
+            tolerated.put_pixel(x, y, *expected.get_pixel(x, y));
+        }
+    }
+    tolerated
+}
+
+/// The importance map, as the weight of each pixel of the images, which must be the same size as
+/// it and each other.
+fn importance_weights(
+    expected: &image::RgbaImage,
+    actual: &image::RgbaImage,
+    importance_map: &image::GrayImage,
+) -> anyhow::Result<image::GrayImage> {
+    if importance_map.dimensions() != actual.dimensions() || expected.dimensions() != actual.dimensions() {
+        anyhow::bail!(
+            "the importance map is {}x{} but the images are {}x{} and {}x{}",
+            importance_map.width(),
+            importance_map.height(),
+            expected.width(),
+            expected.height(),
+            actual.width(),
+            actual.height()
+        );
+    }
+    if importance_map.pixels().all(|p| p[0] == 0) {
+        anyhow::bail!("the importance map gives no pixel any importance");
+    }
+    Ok(importance_map.clone())
+}
+
+/// Replace the pixels of `actual` where `expected` is fully transparent with those of `expected`,
+/// so only the shape the reference's alpha covers affects the score. Images of different sizes
+/// are left alone for the metric to reject.
+fn mask_by_reference_alpha(expected: &image::RgbaImage, actual: &image::RgbaImage) -> anyhow::Result<image::RgbaImage> {
+    if expected.dimensions() != actual.dimensions() {
+        return Ok(actual.clone());
+    }
+    if expected.pixels().all(|pixel| pixel[3] == 0) {
+        anyhow::bail!("the expected image is entirely transparent, so its alpha masks out every pixel");
+    }
+
+    let mut masked = actual.clone();
+    for (pixel, expected_pixel) in masked.pixels_mut().zip(expected.pixels()) {
+        if expected_pixel[3] == 0 {
+            *pixel = *expected_pixel;
+        }
+    }
+    Ok(masked)
+}
+
+/// Replace the pixels of `actual` within the regions with those of `expected`, so differences
+/// there can't affect the score. Images of different sizes are left alone for the metric to
+/// reject.
+fn mask_regions(expected: &image::RgbaImage, actual: &image::RgbaImage, regions: &[crate::Rect]) -> image::RgbaImage {
+    let mut masked = actual.clone();
+    if expected.dimensions() != actual.dimensions() {
+        return masked;
+    }
+
+    let (width, height) = actual.dimensions();
+    for region in regions {
+        for y in region.y.min(height)..region.y.saturating_add(region.height).min(height) {
+            for x in region.x.min(width)..region.x.saturating_add(region.width).min(width) {
+                masked.put_pixel(x, y, *expected.get_pixel(x, y));
+            }
+        }
+    }
+    masked
+}
+
+/// Crop `pixels` off every edge of the image.
+fn crop_border(image: &image::RgbaImage, pixels: u32) -> anyhow::Result<image::RgbaImage> {
+    let (width, height) = image.dimensions();
+    if pixels.saturating_mul(2) >= width || pixels.saturating_mul(2) >= height {
+        anyhow::bail!(
+            "cannot ignore a border of {} pixels on a {}x{} image",
+            pixels,
+            width,
+            height
+        );
+    }
+    Ok(image::imageops::crop_imm(image, pixels, pixels, width - 2 * pixels, height - 2 * pixels).to_image())
+}
+
+/// Convert the color of the image from sRGB to linear light, leaving alpha alone.
+fn linearize(image: &mut image::RgbaImage) {
+    let lookup: [u8; 256] = std::array::from_fn(|value| {
+        let value = value as f32 / 255.0;
+        let linear = if value <= 0.04045 {
+            value / 12.92
+        } else {
+            ((value + 0.055) / 1.055).powf(2.4)
+        };
+        (linear * 255.0).round() as u8
+    });
+    for pixel in image.pixels_mut() {
+        for channel in &mut pixel.0[..3] {
+            *channel = lookup[*channel as usize];
+        }
+    }
+}
+
+/// Make every pixel fully opaque, keeping its color.
+fn make_opaque(image: &mut image::RgbaImage) {
+    for pixel in image.pixels_mut() {
+        pixel[3] = 255;
+    }
+}
+
+/// Zero the color of fully transparent pixels.
+fn zero_transparent(image: &mut image::RgbaImage) {
+    for pixel in image.pixels_mut() {
+        if pixel[3] == 0 {
+            *pixel = image::Rgba([0, 0, 0, 0]);
+        }
+    }
+}
+
+/// Alpha-composite the image over the background color, leaving it fully opaque.
+fn composite_over(image: &mut image::RgbaImage, background: image::Rgb<u8>) {
+    for pixel in image.pixels_mut() {
+        let alpha = pixel[3] as u16;
+        for (channel, background) in pixel.0[..3].iter_mut().zip(background.0) {
+            *channel = ((*channel as u16 * alpha + background as u16 * (255 - alpha) + 127) / 255) as u8;
+        }
+        pixel[3] = 255;
+    }
+}
+
+/// Convert the image from straight to premultiplied alpha.
+fn premultiply_alpha(image: &mut image::RgbaImage) {
+    for pixel in image.pixels_mut() {
+        let alpha = pixel[3] as u16;
+        for channel in &mut pixel.0[..3] {
+            *channel = ((*channel as u16 * alpha + 127) / 255) as u8;
+        }
+    }
+}
+
+/// How far past the pixels that differ [`focus_on_changes`] keeps, so windowed metrics like SSIM
+/// still see the context around a change.
+const FOCUS_PADDING: u32 = 8;
+
+/// Crop both images, and their weights, to the bounding box of the pixels that differ, padded by
+/// [`FOCUS_PADDING`], or to a patch of the same size in the corner if none do. Images of
+/// different sizes are left alone for the metric to reject.
+pub(crate) fn focus_on_changes(prepared: &mut Prepared<'_>) {
+    let (expected, actual) = (&prepared.expected, &prepared.actual);
+    if expected.dimensions() != actual.dimensions() {
+        return;
+    }
+    let (width, height) = expected.dimensions();
+
+    let mut bounds: Option<(u32, u32, u32, u32)> = None;
+    for ((x, y, e), a) in expected.enumerate_pixels().zip(actual.pixels()) {
+        if e != a {
+            let (min_x, min_y, max_x, max_y) = bounds.unwrap_or((x, y, x, y));
+            bounds = Some((min_x.min(x), min_y.min(y), max_x.max(x), max_y.max(y)));
+        }
+    }
+    let (min_x, min_y, max_x, max_y) = bounds.unwrap_or((0, 0, FOCUS_PADDING, FOCUS_PADDING));
+    let (min_x, min_y) = (min_x.saturating_sub(FOCUS_PADDING), min_y.saturating_sub(FOCUS_PADDING));
+    let max_x = max_x.saturating_add(FOCUS_PADDING).min(width.saturating_sub(1));
+    let max_y = max_y.saturating_add(FOCUS_PADDING).min(height.saturating_sub(1));
+
+    let (crop_width, crop_height) = (max_x - min_x + 1, max_y - min_y + 1);
+    prepared.expected =
+        Cow::Owned(image::imageops::crop_imm(&**expected, min_x, min_y, crop_width, crop_height).to_image());
+    prepared.actual =
+        Cow::Owned(image::imageops::crop_imm(&**actual, min_x, min_y, crop_width, crop_height).to_image());
+    if let Some(weights) = &mut prepared.weights {
+        *weights = image::imageops::crop_imm(weights, min_x, min_y, crop_width, crop_height).to_image();
+    }
+}
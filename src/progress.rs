@@ -0,0 +1,12 @@
+//! Progress reporting for batches of comparisons.
+
+/// A progress bar on stderr for `len` comparisons, showing how many are done and an ETA from the
+/// average time each has taken. It can be advanced from any thread, and draws nothing when
+/// stderr isn't a terminal.
+pub(crate) fn bar(len: usize) -> indicatif::ProgressBar {
+    let bar = indicatif::ProgressBar::new(len as u64);
+    if let Ok(style) = indicatif::ProgressStyle::with_template("{bar:40} {pos}/{len} comparisons, ETA {eta}") {
+        bar.set_style(style);
+    }
+    bar
+}
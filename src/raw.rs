@@ -0,0 +1,80 @@
+//! References stored as their raw decoded RGBA8 bytes rather than as PNGs, so a reference can't
+//! change just because the PNG encoder that wrote it did.
+
+/// The size of the header of a raw reference: its width and height as little-endian `u32`s.
+const HEADER_LEN: usize = 8;
+
+/// Decode a raw reference: its width and height as little-endian `u32`s, then its pixels as
+/// RGBA8, row by row.
+pub(crate) fn decode(bytes: &[u8]) -> anyhow::Result<image::RgbaImage> {
+    anyhow::ensure!(
+        bytes.len() >= HEADER_LEN,
+        "it is {} bytes, too short for the width and height",
+        bytes.len()
+    );
+    let (header, pixels) = bytes.split_at(HEADER_LEN);
+    let width = u32::from_le_bytes([header[0], header[1], header[2], header[3]]);
+    let height = u32::from_le_bytes([header[4], header[5], header[6], header[7]]);
+    let expected_len = (width as usize)
+        .checked_mul(height as usize)
+        .and_then(|len| len.checked_mul(4))
+        .ok_or_else(|| anyhow::anyhow!("its size of {width}x{height} is too large"))?;
+    anyhow::ensure!(
+        pixels.len() == expected_len,
+        "it has {} bytes of pixels but a {}x{} image has {}",
+        pixels.len(),
+        width,
+        height,
+        expected_len
+    );
+    image::RgbaImage::from_raw(width, height, pixels.to_vec())
+        .ok_or_else(|| anyhow::anyhow!("its pixels don't fit a {width}x{height} image"))
+}
+
+/// Encode the image as a raw reference, see [`decode`].
+fn encode(image: &image::RgbaImage) -> Vec<u8> {
+    let mut bytes = Vec::with_capacity(HEADER_LEN + image.as_raw().len());
+    bytes.extend_from_slice(&image.width().to_le_bytes());
+    bytes.extend_from_slice(&image.height().to_le_bytes());
+    bytes.extend_from_slice(image.as_raw());
+    bytes
+}
+
+/// Read the raw reference at `path`.
+pub(crate) fn read(path: &std::path::Path) -> anyhow::Result<image::RgbaImage> {
+    let bytes = std::fs::read(path)?;
+    decode(&bytes).map_err(|e| anyhow::anyhow!("unable to decode raw reference (`{}`): {}", path.display(), e))
+}
+
+/// Load the raw reference at `path`, treating a nonexistent file like an empty image with the
+/// size of `actual` unless `require_reference` is set, like [`crate::load_reference`].
+pub(crate) fn load(
+    path: &std::path::Path,
+    actual: &image::DynamicImage,
+    require_reference: bool,
+) -> anyhow::Result<image::DynamicImage> {
+    crate::checksum::verify(path)?;
+    match std::fs::read(path) {
+        Ok(bytes) => decode(&bytes)
+            .map(image::DynamicImage::ImageRgba8)
+            .map_err(|e| anyhow::anyhow!("unable to decode raw reference (`{}`): {}", path.display(), e)),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound && require_reference => anyhow::bail!(
+            "image (`{}`) does not exist, set {}=overwrite to create it",
+            path.display(),
+            crate::CRATE_ENV_VAR
+        ),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+            Ok(image::DynamicImage::new_rgba8(actual.width(), actual.height()))
+        }
+        Err(e) => anyhow::bail!("unable to read contents of {}: {}", path.display(), e),
+    }
+}
+
+/// Save the image as a raw reference at `path`, creating any missing parent directories.
+pub(crate) fn save(image: &image::DynamicImage, path: &std::path::Path) -> anyhow::Result<()> {
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    std::fs::write(path, encode(&crate::rgba8(image)))
+        .map_err(|e| anyhow::anyhow!("unable to write image to {}: {}", path.display(), e))
+}
@@ -0,0 +1,254 @@
+//! Collects the outcome of every comparison so it can be reported on at the end of a run.
+
+use std::{
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Mutex, OnceLock,
+    },
+    time::Duration,
+};
+
+/// The environment variable holding the path the JUnit XML report is written to.
+pub(crate) const JUNIT_ENV_VAR: &str = "TWENTY_TWENTY_JUNIT";
+
+/// The environment variable that turns on logging how long each comparison took to stderr, when
+/// set to `1` or `true`.
+pub(crate) const TIMINGS_ENV_VAR: &str = "TWENTY_TWENTY_TIMINGS";
+
+/// How long the parts of a single comparison took.
+#[derive(Clone, Copy, Debug, Default)]
+pub(crate) struct Timing {
+    /// Loading and decoding the references.
+    pub(crate) decode: Duration,
+    /// Scoring the actual image against the references.
+    pub(crate) compare: Duration,
+}
+
+/// The outcome of a single comparison.
+struct Record {
+    /// The path of the reference the comparison was made against.
+    path: PathBuf,
+    /// Why the comparison failed, if it did.
+    failure: Option<String>,
+    /// How long the comparison took, if it was timed.
+    timing: Option<Timing>,
+}
+
+/// Every comparison made by this process so far.
+static RECORDS: Mutex<Vec<Record>> = Mutex::new(Vec::new());
+
+/// A comparison that was scored, whether it passed or failed, as returned by [`take_results`].
+#[derive(Clone, Debug)]
+pub struct ComparisonRecord {
+    /// The path of the reference the comparison was made against, or the URL of a remote one.
+    pub path: PathBuf,
+    /// The score of the comparison, in the units of its metric.
+    pub score: f64,
+    /// The minimum score the comparison had to reach to pass.
+    pub threshold: f64,
+    /// Whether the comparison passed.
+    pub passed: bool,
+    /// The `TWENTY_TWENTY` mode the comparison was made in.
+    pub mode: crate::Mode,
+    /// When the comparison was made.
+    pub timestamp: std::time::SystemTime,
+}
+
+/// Every comparison scored by this process since the last [`take_results`], once
+/// [`collect_results`] has been called.
+static RESULTS: Mutex<Vec<ComparisonRecord>> = Mutex::new(Vec::new());
+
+/// Whether scored comparisons are kept in [`RESULTS`].
+static COLLECT_RESULTS: AtomicBool = AtomicBool::new(false);
+
+/// A callback invoked with the path, score, and outcome of every comparison.
+type ResultCallback = Box<dyn Fn(&Path, f64, bool) + Send + Sync>;
+
+static RESULT_CALLBACK: OnceLock<ResultCallback> = OnceLock::new();
+
+/// Register a callback to be invoked after every comparison with the path of the reference, the
+/// score, and whether the comparison passed, e.g. to print a running tally.
+/// Only one callback can be registered per process; registering another is an error.
+pub fn set_result_callback<F>(callback: F) -> anyhow::Result<()>
+where
+    F: Fn(&Path, f64, bool) + Send + Sync + 'static,
+{
+    RESULT_CALLBACK
+        .set(Box::new(callback))
+        .map_err(|_| anyhow::anyhow!("a result callback has already been registered"))
+}
+
+/// Start keeping every comparison this process scores from now on, for [`take_results`] and
+/// [`print_summary`].
+/// Collection is off by default so that long runs that never look at the results don't keep
+/// every one of them in memory.
+pub fn collect_results() {
+    COLLECT_RESULTS.store(true, Ordering::Relaxed);
+}
+
+/// Invoke the registered result callback, if any, and collect the comparison for
+/// [`take_results`] and [`print_summary`] if [`collect_results`] has been called.
+pub(crate) fn notify(path: &Path, score: f64, threshold: f64, passed: bool, mode: crate::Mode) {
+    if let Some(callback) = RESULT_CALLBACK.get() {
+        callback(path, score, passed);
+    }
+    if !COLLECT_RESULTS.load(Ordering::Relaxed) {
+        return;
+    }
+    RESULTS
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .push(ComparisonRecord {
+            path: path.to_path_buf(),
+            score,
+            threshold,
+            passed,
+            mode,
+            timestamp: std::time::SystemTime::now(),
+        });
+}
+
+/// Take every comparison scored by this process since the last call, passed or failed, e.g. to
+/// check an invariant across a whole suite, like no score falling below 0.98 even where the
+/// thresholds are lower.
+/// Only comparisons made after [`collect_results`] are included, so call it first, e.g. at the
+/// start of a custom test harness.
+/// Comparisons that fail before they can be scored, e.g. because the reference doesn't decode,
+/// aren't included. Taken comparisons no longer appear in [`print_summary`].
+pub fn take_results() -> Vec<ComparisonRecord> {
+    std::mem::take(&mut *RESULTS.lock().unwrap_or_else(|e| e.into_inner()))
+}
+
+/// Print a table of every comparison scored by this process since the last [`take_results`] to
+/// stderr, failures first and then from the lowest score up, to see the health of every
+/// reference in a run at a glance rather than piecing it together from each test's panic.
+/// Call [`collect_results`] at the start of a run and this at the end, e.g. from the last test or
+/// a custom test harness.
+/// Like [`flush_reports`], this only sees the comparisons made by the current process.
+pub fn print_summary() {
+    let results = RESULTS.lock().unwrap_or_else(|e| e.into_inner());
+    eprint!("{}", render_summary(&results));
+}
+
+/// Render the scores as a table, failures first and then from the lowest score up.
+pub(crate) fn render_summary(scores: &[ComparisonRecord]) -> String {
+    let mut sorted: Vec<&ComparisonRecord> = scores.iter().collect();
+    sorted.sort_by(|a, b| a.passed.cmp(&b.passed).then(a.score.total_cmp(&b.score)));
+    let failures = scores.iter().filter(|score| !score.passed).count();
+    let mut summary = format!("twenty-twenty: {} comparisons, {} failed\n", scores.len(), failures);
+    for score in sorted {
+        summary.push_str(&format!(
+            "  {}  {:>10.4}  {}\n",
+            if score.passed { "pass" } else { "FAIL" },
+            score.score,
+            score.path.display()
+        ));
+    }
+    summary
+}
+
+/// Record the outcome of a comparison.
+///
+/// This is a no-op unless `TWENTY_TWENTY_JUNIT` is set. The report itself is only written by
+/// [`flush_reports`].
+pub(crate) fn record<T>(path: &Path, result: &anyhow::Result<T>) {
+    record_timed(path, result, None);
+}
+
+/// Record the outcome of a comparison along with how long it took, which is logged to stderr if
+/// `TWENTY_TWENTY_TIMINGS` is set and goes in the JUnit report as the time of the test case.
+pub(crate) fn record_timed<T>(path: &Path, result: &anyhow::Result<T>, timing: Option<Timing>) {
+    if let Some(timing) = timing.filter(|_| timings_enabled()) {
+        eprintln!(
+            "twenty-twenty: `{}` took {}ms to decode and {}ms to compare",
+            path.display(),
+            timing.decode.as_millis(),
+            timing.compare.as_millis()
+        );
+    }
+
+    if std::env::var_os(JUNIT_ENV_VAR).is_none() {
+        return;
+    }
+
+    RECORDS.lock().unwrap_or_else(|e| e.into_inner()).push(Record {
+        path: path.to_path_buf(),
+        failure: result.as_ref().err().map(|e| e.to_string()),
+        timing,
+    });
+}
+
+/// Whether the time taken by each comparison should be logged.
+fn timings_enabled() -> bool {
+    matches!(std::env::var(TIMINGS_ENV_VAR).as_deref(), Ok("1") | Ok("true"))
+}
+
+/// Write a JUnit XML report of every comparison made so far to the file named by the
+/// `TWENTY_TWENTY_JUNIT` environment variable.
+/// Call it once at the end of a run, e.g. from the last test or a custom test harness; nothing is
+/// written until it is called.
+/// Each reference path becomes a test case, and failed comparisons carry the score and the
+/// threshold they were held to as the failure message.
+/// Records are collected per process, so test runners that run each test in its own process
+/// will only see the comparisons made by the last one.
+/// Does nothing if `TWENTY_TWENTY_JUNIT` is not set.
+pub fn flush_reports() -> anyhow::Result<()> {
+    let Some(report_path) = std::env::var_os(JUNIT_ENV_VAR) else {
+        return Ok(());
+    };
+
+    let records = RECORDS.lock().unwrap_or_else(|e| e.into_inner());
+    std::fs::write(&report_path, render_junit(&records)).map_err(|e| {
+        anyhow::anyhow!(
+            "unable to write JUnit report to {}: {}",
+            Path::new(&report_path).display(),
+            e
+        )
+    })
+}
+
+fn render_junit(records: &[Record]) -> String {
+    let failures = records.iter().filter(|r| r.failure.is_some()).count();
+    let mut xml = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    xml.push_str(&format!(
+        "<testsuite name=\"twenty-twenty\" tests=\"{}\" failures=\"{}\">\n",
+        records.len(),
+        failures
+    ));
+    for record in records {
+        let name = escape_xml(&record.path.display().to_string());
+        let time = record
+            .timing
+            .map(|timing| format!(" time=\"{:.3}\"", (timing.decode + timing.compare).as_secs_f64()))
+            .unwrap_or_default();
+        match &record.failure {
+            Some(failure) => {
+                let failure = escape_xml(failure);
+                xml.push_str(&format!(
+                    "  <testcase classname=\"twenty-twenty\" name=\"{name}\"{time}>\n    <failure message=\"{failure}\">{failure}</failure>\n  </testcase>\n"
+                ));
+            }
+            None => xml.push_str(&format!(
+                "  <testcase classname=\"twenty-twenty\" name=\"{name}\"{time}/>\n"
+            )),
+        }
+    }
+    xml.push_str("</testsuite>\n");
+    xml
+}
+
+fn escape_xml(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
@@ -0,0 +1,63 @@
+//! Options for comparisons against a single reference kept in a file next to it rather than in
+//! code, so tolerances can be tuned without touching the tests.
+
+use std::path::{Path, PathBuf};
+
+/// The options a sidecar can set, each overriding what the test passed in.
+#[derive(serde::Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Sidecar {
+    /// The lowest score the comparison passes with.
+    #[serde(default)]
+    min: Option<f64>,
+    /// The metric to score the images with.
+    #[serde(default)]
+    metric: Option<crate::Metric>,
+    /// The path of a grayscale importance map, see [`crate::Config::importance_map`].
+    #[serde(default)]
+    mask: Option<PathBuf>,
+    /// The regions whose differences are ignored, see [`crate::Config::mask_region`].
+    #[serde(default)]
+    mask_regions: Vec<crate::Rect>,
+}
+
+/// The path of the sidecar of the reference at `path`, so `foo.png` has its options in
+/// `foo.png.tt.toml`.
+pub(crate) fn sidecar_path(path: &Path) -> PathBuf {
+    let mut sidecar = path.as_os_str().to_owned();
+    sidecar.push(".tt.toml");
+    PathBuf::from(sidecar)
+}
+
+/// The threshold and config to compare against the reference at `path` with, after applying its
+/// sidecar to the ones given, or `None` if it has no sidecar.
+pub(crate) fn load(
+    path: &Path,
+    min_permissible_similarity: f64,
+    config: &crate::Config,
+) -> anyhow::Result<Option<(f64, crate::Config)>> {
+    let sidecar_path = sidecar_path(path);
+    let contents = match std::fs::read_to_string(&sidecar_path) {
+        Ok(contents) => contents,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => anyhow::bail!("unable to read sidecar {}: {}", sidecar_path.display(), e),
+    };
+    let sidecar: Sidecar = toml::from_str(&contents)
+        .map_err(|e| anyhow::anyhow!("unable to parse sidecar {}: {}", sidecar_path.display(), e))?;
+
+    let mut config = config.clone();
+    if let Some(metric) = sidecar.metric {
+        config = config.metric(metric);
+    }
+    if let Some(mask) = &sidecar.mask {
+        let mask_path = sidecar_path.parent().unwrap_or(Path::new("")).join(mask);
+        let mask = image::open(&mask_path)
+            .map_err(|e| anyhow::anyhow!("unable to read mask {}: {}", mask_path.display(), e))?;
+        config = config.importance_map(mask.to_luma8());
+    }
+    for region in sidecar.mask_regions {
+        config = config.mask_region(region);
+    }
+
+    Ok(Some((sidecar.min.unwrap_or(min_permissible_similarity), config)))
+}
@@ -0,0 +1,82 @@
+//! Comparing against references defined as SVGs.
+
+/// Compare the SVG file, rendered at the size of the image provided, to the image provided.
+/// This lets you keep compact, diffable SVG references in git rather than PNGs.
+/// If the two are less similar than the `min_permissible_similarity` threshold,
+/// the test will fail.
+/// The `min_permissible_similarity` is a float between 0 and 1.
+/// If the images are the exact same, the score will be 1.
+/// As the rendered image can't be written back to an SVG, the `TWENTY_TWENTY` modes have no
+/// effect.
+#[track_caller]
+pub fn assert_image_against_svg<P: AsRef<std::path::Path>>(
+    svg_path: P,
+    actual: &image::DynamicImage,
+    min_permissible_similarity: f64,
+) {
+    if let Err(e) = assert_image_against_svg_impl(svg_path.as_ref(), actual, min_permissible_similarity) {
+        panic!("assertion failed: {e}")
+    }
+}
+
+fn assert_image_against_svg_impl(
+    svg_path: &std::path::Path,
+    actual: &image::DynamicImage,
+    min_permissible_similarity: f64,
+) -> anyhow::Result<()> {
+    if crate::Config::default().skipped() {
+        return Ok(());
+    }
+
+    let config = crate::Config::default();
+    let svg_path = crate::resolve_reference_path(svg_path);
+    let data = std::fs::read(&svg_path)
+        .map_err(|e| anyhow::anyhow!("unable to read contents of {}: {}", svg_path.display(), e))?;
+    let expected = render_svg(&data, actual.width(), actual.height())
+        .map_err(|e| anyhow::anyhow!("unable to render {}: {}", svg_path.display(), e))?;
+
+    let score = crate::score(&expected, actual, min_permissible_similarity, &config)?;
+    crate::report::notify(
+        &svg_path,
+        score,
+        min_permissible_similarity,
+        score >= min_permissible_similarity,
+        crate::Mode::from_env(),
+    );
+    if score < min_permissible_similarity {
+        anyhow::bail!(
+            "image (`{}`) {}",
+            svg_path.display(),
+            config.metric.describe_mismatch(score, min_permissible_similarity)
+        )
+    }
+
+    Ok(())
+}
+
+/// Render the SVG, stretched to fill the given size.
+fn render_svg(data: &[u8], width: u32, height: u32) -> anyhow::Result<image::DynamicImage> {
+    let tree = resvg::usvg::Tree::from_data(data, &resvg::usvg::Options::default())?;
+    let Some(mut pixmap) = resvg::tiny_skia::Pixmap::new(width, height) else {
+        anyhow::bail!("cannot render an SVG at {}x{}", width, height);
+    };
+
+    let size = tree.size();
+    let transform = resvg::tiny_skia::Transform::from_scale(width as f32 / size.width(), height as f32 / size.height());
+    resvg::render(&tree, transform, &mut pixmap.as_mut());
+
+    // The pixmap is premultiplied, while images are compared with straight alpha.
+    let pixels = pixmap
+        .pixels()
+        .iter()
+        .flat_map(|pixel| {
+            let color = pixel.demultiply();
+            [color.red(), color.green(), color.blue(), color.alpha()]
+        })
+        .collect();
+    let Some(image) = image::RgbaImage::from_raw(width, height, pixels) else {
+        anyhow::bail!("the container was not big enough as per: https://docs.rs/image/latest/image/struct.ImageBuffer.html#method.from_raw");
+    };
+
+    Ok(image::DynamicImage::ImageRgba8(image))
+}
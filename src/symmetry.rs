@@ -0,0 +1,61 @@
+//! Checking that an image is symmetric, with no reference at all.
+
+/// An axis an image can be mirrored about.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Axis {
+    /// The vertical line through the middle of the image, so the left half mirrors the right.
+    Vertical,
+    /// The horizontal line through the middle of the image, so the top half mirrors the bottom.
+    Horizontal,
+}
+
+/// Compare one half of the image to the mirror image of the other half, split about `axis`, e.g.
+/// to check that a mirrored UI or a procedural output that is symmetric by construction really
+/// is. The middle row or column of an image with an odd size belongs to neither half.
+/// If the halves are less similar than the `min_permissible_similarity` threshold,
+/// the test will fail.
+/// The `min_permissible_similarity` is a float between 0 and 1.
+/// If the halves are exact mirror images, the score will be 1.
+/// There is no reference, so the `TWENTY_TWENTY` modes other than `off` have no effect, and the
+/// comparison is reported to [`crate::set_result_callback`] and [`crate::take_results`] with the
+/// path `<vertical symmetry>` or `<horizontal symmetry>`.
+#[track_caller]
+pub fn assert_self_symmetry(actual: &image::DynamicImage, axis: Axis, min_permissible_similarity: f64) {
+    if let Err(e) = assert_self_symmetry_impl(actual, axis, min_permissible_similarity) {
+        panic!("assertion failed: {e}")
+    }
+}
+
+fn assert_self_symmetry_impl(
+    actual: &image::DynamicImage,
+    axis: Axis,
+    min_permissible_similarity: f64,
+) -> anyhow::Result<()> {
+    if crate::Config::default().skipped() {
+        return Ok(());
+    }
+
+    let (width, height) = (actual.width(), actual.height());
+    let (first, second) = match axis {
+        Axis::Vertical => (
+            actual.crop_imm(0, 0, width / 2, height),
+            actual.crop_imm(width - width / 2, 0, width / 2, height).fliph(),
+        ),
+        Axis::Horizontal => (
+            actual.crop_imm(0, 0, width, height / 2),
+            actual.crop_imm(0, height - height / 2, width, height / 2).flipv(),
+        ),
+    };
+
+    let axis = match axis {
+        Axis::Vertical => "vertical",
+        Axis::Horizontal => "horizontal",
+    };
+    crate::assert_against_unsaved_reference(
+        &format!("<{axis} symmetry>"),
+        &format!("isn't symmetric about its {axis} axis"),
+        &first,
+        &second,
+        min_permissible_similarity,
+    )
+}
@@ -1,4 +1,4 @@
-use twenty_twenty::{assert_h264_frame, assert_image};
+use twenty_twenty::{assert_h264_frame, assert_image, assert_image_against_bytes, assert_rgba8};
 
 #[test]
 fn good() {
@@ -13,18 +13,1211 @@ fn bad() {
     assert_image("tests/dog2.png", &actual, 1.0);
 }
 
+#[test]
+fn good_rgba8() {
+    let actual = image::io::Reader::open("tests/dog1.png")
+        .unwrap()
+        .decode()
+        .unwrap()
+        .to_rgba8();
+    assert_rgba8("tests/dog1.png", actual.width(), actual.height(), actual.as_raw(), 1.0);
+}
+
+#[test]
+#[should_panic]
+fn bad_rgba8_buffer_length() {
+    assert_rgba8("tests/dog1.png", 10, 10, &[0; 12], 1.0);
+}
+
+#[test]
+fn good_against_bytes() {
+    let actual = image::io::Reader::open("tests/dog1.png").unwrap().decode().unwrap();
+    assert_image_against_bytes(include_bytes!("dog1.png"), &actual, 1.0);
+}
+
+#[test]
+#[should_panic(expected = "image doesn't match its reference bytes, score is")]
+fn bad_against_bytes() {
+    assert_image_against_bytes(include_bytes!("dog1.png"), &changed_dog(96, 96), 1.0);
+}
+
+/// A 16x16 checkerboard of 8x8 black and white squares, as a base64 PNG.
+const CHECKERBOARD_BASE64: &str = "iVBORw0KGgoAAAANSUhEUgAAABAAAAAQCAYAAAAf8/9hAAAAIElEQVR42mNgYGD4jw8TAgyjBgwLA4iwAT8eNWA4GAAAfKJ9nxUHEUIAAAAASUVORK5CYII=";
+
+#[test]
+fn good_against_base64() {
+    let actual = image::RgbaImage::from_fn(16, 16, |x, y| {
+        let value = if (x / 8 + y / 8) % 2 == 0 { 0 } else { 255 };
+        image::Rgba([value, value, value, 255])
+    });
+    twenty_twenty::assert_image_against_base64(CHECKERBOARD_BASE64, &actual.into(), 1.0);
+}
+
+#[test]
+#[should_panic(expected = "unable to decode reference image from base64")]
+fn bad_base64() {
+    let actual = image::RgbaImage::new(16, 16);
+    twenty_twenty::assert_image_against_base64("not base64!", &actual.into(), 1.0);
+}
+
+#[test]
+fn tiles() {
+    let actual = image::io::Reader::open("tests/dog1.png").unwrap().decode().unwrap();
+    let tiles = twenty_twenty::compare_image_tiles("tests/dog1.png", &actual, 64).unwrap();
+    assert_eq!(
+        tiles.len() as u32,
+        actual.width().div_ceil(64) * actual.height().div_ceil(64)
+    );
+    assert!(tiles.iter().all(|(_, score)| *score == 1.0));
+}
+
+#[test]
+fn ignore_transparent() {
+    std::fs::create_dir_all("tests/tmp").unwrap();
+    image::RgbaImage::new(32, 32).save("tests/tmp/transparent.png").unwrap();
+    let actual = image::RgbaImage::from_fn(32, 32, |x, y| image::Rgba([x as u8 * 8, y as u8 * 8, 255, 0]));
+    twenty_twenty::Config::new().ignore_transparent(true).assert_image(
+        "tests/tmp/transparent.png",
+        &image::DynamicImage::ImageRgba8(actual),
+        1.0,
+    );
+}
+
+#[test]
+fn max_similarity() {
+    let actual = image::io::Reader::open("tests/initial-grid.png")
+        .unwrap()
+        .decode()
+        .unwrap();
+    twenty_twenty::Config::new()
+        .max_similarity(0.999)
+        .assert_image("tests/multiple-frames.png", &actual, 0.0);
+}
+
+#[test]
+#[should_panic(expected = "doesn't seem to have taken effect")]
+fn too_similar() {
+    let actual = image::io::Reader::open("tests/dog1.png").unwrap().decode().unwrap();
+    twenty_twenty::Config::new()
+        .max_similarity(0.999)
+        .assert_image("tests/dog1.png", &actual, 0.0);
+}
+
+#[test]
+fn ignore_alpha() {
+    let mut actual = image::io::Reader::open("tests/dog1.png")
+        .unwrap()
+        .decode()
+        .unwrap()
+        .to_rgba8();
+    for pixel in actual.pixels_mut() {
+        pixel[3] = 0;
+    }
+    twenty_twenty::Config::new().ignore_alpha(true).assert_image(
+        "tests/dog1.png",
+        &image::DynamicImage::ImageRgba8(actual),
+        1.0,
+    );
+}
+
+#[test]
+fn premultiply_alpha_implies_ignore_transparent() {
+    std::fs::create_dir_all("tests/tmp").unwrap();
+    image::RgbaImage::new(32, 32)
+        .save("tests/tmp/premultiplied.png")
+        .unwrap();
+    let actual = image::RgbaImage::from_fn(32, 32, |x, y| image::Rgba([x as u8 * 8, y as u8 * 8, 255, 0]));
+    twenty_twenty::Config::new().premultiply_alpha(true).assert_image(
+        "tests/tmp/premultiplied.png",
+        &image::DynamicImage::ImageRgba8(actual),
+        1.0,
+    );
+}
+
+#[test]
+#[should_panic(expected = "does not exist")]
+fn require_reference() {
+    let actual = image::io::Reader::open("tests/dog1.png").unwrap().decode().unwrap();
+    twenty_twenty::Config::new()
+        .require_reference(true)
+        .assert_image("tests/tmp/missing.png", &actual, 0.0);
+}
+
+#[test]
+fn good_psnr() {
+    let actual = image::io::Reader::open("tests/dog1.png").unwrap().decode().unwrap();
+    twenty_twenty::assert_image_psnr("tests/dog1.png", &actual, 40.0);
+}
+
+#[test]
+#[should_panic(expected = "PSNR")]
+fn bad_psnr() {
+    std::fs::create_dir_all("tests/tmp").unwrap();
+    image::RgbaImage::from_pixel(32, 32, image::Rgba([0, 0, 0, 255]))
+        .save("tests/tmp/black.png")
+        .unwrap();
+    let actual = image::RgbaImage::from_pixel(32, 32, image::Rgba([255, 255, 255, 255]));
+    twenty_twenty::assert_image_psnr("tests/tmp/black.png", &image::DynamicImage::ImageRgba8(actual), 40.0);
+}
+
+#[test]
+fn good_rms() {
+    let actual = image::io::Reader::open("tests/dog1.png").unwrap().decode().unwrap();
+    twenty_twenty::assert_image_rms("tests/dog1.png", &actual, 0.0);
+}
+
+#[test]
+#[should_panic(expected = "RMS error is `0.43")]
+fn bad_rms() {
+    std::fs::create_dir_all("tests/tmp").unwrap();
+    image::RgbaImage::from_pixel(32, 32, image::Rgba([0, 0, 0, 255]))
+        .save("tests/tmp/rms-black.png")
+        .unwrap();
+    let actual = image::RgbaImage::from_pixel(32, 32, image::Rgba([128, 128, 128, 255]));
+    twenty_twenty::assert_image_rms("tests/tmp/rms-black.png", &image::DynamicImage::ImageRgba8(actual), 0.1);
+}
+
+#[test]
+fn take_results() {
+    std::fs::create_dir_all("tests/tmp").unwrap();
+    std::fs::copy("tests/dog1.png", "tests/tmp/take-results.png").unwrap();
+    let actual = image::io::Reader::open("tests/dog1.png").unwrap().decode().unwrap();
+    twenty_twenty::collect_results();
+    assert_image("tests/tmp/take-results.png", &actual, 0.9);
+    assert_image_against_bytes(include_bytes!("dog1.png"), &actual, 0.9);
+
+    // Other tests run alongside this one, so look for this comparison among theirs.
+    let results = twenty_twenty::take_results();
+    let result = results
+        .iter()
+        .find(|result| result.path == std::path::Path::new("tests/tmp/take-results.png"))
+        .unwrap();
+    assert_eq!(result.score, 1.0);
+    assert_eq!(result.threshold, 0.9);
+    assert!(result.passed);
+    // References with no file of their own are reported under a name in its place.
+    assert!(results
+        .iter()
+        .any(|result| result.path == std::path::Path::new("<bytes>") && result.passed));
+}
+
+#[test]
+fn result_callback() {
+    static CALLS: std::sync::atomic::AtomicUsize = std::sync::atomic::AtomicUsize::new(0);
+    twenty_twenty::set_result_callback(|path, score, passed| {
+        if path == std::path::Path::new("tests/dog1.png") && score == 1.0 && passed {
+            CALLS.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+        }
+    })
+    .unwrap();
+
+    let actual = image::io::Reader::open("tests/dog1.png").unwrap().decode().unwrap();
+    assert_image("tests/dog1.png", &actual, 1.0);
+    assert!(CALLS.load(std::sync::atomic::Ordering::SeqCst) >= 1);
+}
+
+#[test]
+fn good_ms_ssim() {
+    let actual = image::io::Reader::open("tests/initial-grid.png")
+        .unwrap()
+        .decode()
+        .unwrap();
+    twenty_twenty::assert_image_with_metric("tests/initial-grid.png", &actual, 0.999, twenty_twenty::Metric::MsSsim);
+}
+
+#[test]
+fn diff_output() {
+    let actual = image::io::Reader::open("tests/dog1.png").unwrap().decode().unwrap();
+    twenty_twenty::Config::new()
+        .diff_output("tests/tmp/diff/dog1.png")
+        .assert_image("tests/dog1.png", &actual, 1.0);
+    let diff = image::io::Reader::open("tests/tmp/diff/dog1.png")
+        .unwrap()
+        .decode()
+        .unwrap();
+    assert_eq!((diff.width(), diff.height()), (actual.width() * 3, actual.height()));
+}
+
+#[test]
+fn histogram_ignores_spatial_structure() {
+    let actual = image::io::Reader::open("tests/dog1.png").unwrap().decode().unwrap();
+    twenty_twenty::assert_image_with_metric(
+        "tests/dog1.png",
+        &actual.fliph(),
+        0.999,
+        twenty_twenty::Metric::Histogram,
+    );
+}
+
+#[test]
+fn good_batch() {
+    let dog = image::io::Reader::open("tests/dog1.png").unwrap().decode().unwrap();
+    let grid = image::io::Reader::open("tests/initial-grid.png")
+        .unwrap()
+        .decode()
+        .unwrap();
+    twenty_twenty::assert_images(&[("tests/dog1.png", &dog, 1.0), ("tests/initial-grid.png", &grid, 1.0)]);
+}
+
+#[test]
+#[should_panic(expected = "1 of 2 comparisons failed")]
+fn bad_batch() {
+    let dog = image::io::Reader::open("tests/dog1.png").unwrap().decode().unwrap();
+    twenty_twenty::assert_images(&[("tests/dog1.png", &dog, 1.0), ("tests/dog2.png", &dog, 1.0)]);
+}
+
+/// dog1 with a 16x16 black square at `(x, y)`, small enough to pass at a threshold of 0.99.
+fn changed_dog(x: u32, y: u32) -> image::DynamicImage {
+    let mut changed = image::io::Reader::open("tests/dog1.png")
+        .unwrap()
+        .decode()
+        .unwrap()
+        .to_rgba8();
+    paint_black_square(&mut changed, x, y);
+    image::DynamicImage::ImageRgba8(changed)
+}
+
+/// Paint a 16x16 black square onto `image` with its top left corner at `(x0, y0)`.
+fn paint_black_square(image: &mut image::RgbaImage, x0: u32, y0: u32) {
+    for y in y0..y0 + 16 {
+        for x in x0..x0 + 16 {
+            image.put_pixel(x, y, image::Rgba([0, 0, 0, 255]));
+        }
+    }
+}
+
+#[test]
+fn manifest() {
+    std::fs::create_dir_all("tests/tmp").unwrap();
+    changed_dog(96, 96).save("tests/tmp/manifest-changed.png").unwrap();
+    image::GrayImage::from_fn(200, 200, |x, y| {
+        let changed = (96..112).contains(&x) && (96..112).contains(&y);
+        image::Luma([if changed { 0 } else { 255 }])
+    })
+    .save("tests/tmp/manifest-mask.png")
+    .unwrap();
+    std::fs::write(
+        "tests/tmp/manifest.json",
+        r#"[
+            { "reference": "../dog1.png", "actual": "../dog1.png", "min": 1.0 },
+            { "reference": "../dog1.png", "actual": "../dog1.png", "min": 60.0, "metric": "psnr" },
+            { "reference": "../dog1.png", "actual": "manifest-changed.png", "min": 1.0, "mask": "manifest-mask.png" }
+        ]"#,
+    )
+    .unwrap();
+    twenty_twenty::assert_from_manifest("tests/tmp/manifest.json");
+}
+
+#[test]
+#[should_panic(expected = "1 of 1 comparisons")]
+fn manifest_unmasked_change() {
+    std::fs::create_dir_all("tests/tmp").unwrap();
+    changed_dog(96, 96).save("tests/tmp/manifest-unmasked.png").unwrap();
+    std::fs::write(
+        "tests/tmp/manifest-unmasked.json",
+        r#"[{ "reference": "../dog1.png", "actual": "manifest-unmasked.png", "min": 1.0 }]"#,
+    )
+    .unwrap();
+    twenty_twenty::assert_from_manifest("tests/tmp/manifest-unmasked.json");
+}
+
+#[test]
+fn compare_image_map() {
+    let actual = image::io::Reader::open("tests/dog1.png").unwrap().decode().unwrap();
+    let (score, map) = twenty_twenty::compare_image_map("tests/dog1.png", &actual).unwrap();
+    assert_eq!(score, 1.0);
+    assert_eq!(map.dimensions(), (actual.width(), actual.height()));
+    assert!(map.pixels().all(|p| (p[0] - 1.0).abs() < 1e-6));
+
+    let (score, map) = twenty_twenty::compare_image_map("tests/dog1.png", &changed_dog(0, 0)).unwrap();
+    assert!(map.get_pixel(4, 4)[0] < 0.5);
+    assert!((map.get_pixel(100, 100)[0] - 1.0).abs() < 1e-6);
+    let mean = map.pixels().map(|p| p[0] as f64).sum::<f64>() / map.pixels().len() as f64;
+    assert_eq!(mean, score);
+    assert_eq!(
+        score,
+        twenty_twenty::assert_image_scored("tests/dog1.png", &changed_dog(0, 0), 0.0)
+    );
+}
+
+#[test]
+fn compare() {
+    let actual = image::io::Reader::open("tests/dog1.png").unwrap().decode().unwrap();
+    let comparison = twenty_twenty::compare("tests/dog1.png", &actual).unwrap();
+    assert_eq!(comparison.score, 1.0);
+    assert_eq!(comparison.expected.to_rgba8(), comparison.actual.to_rgba8());
+    assert!(comparison.diff.is_none());
+}
+
+#[test]
+fn differing_color_types() {
+    std::fs::create_dir_all("tests/tmp").unwrap();
+    let dog = image::io::Reader::open("tests/dog1.png").unwrap().decode().unwrap();
+    let luma = image::DynamicImage::ImageLuma8(dog.to_luma8());
+    luma.save("tests/tmp/luma-reference.png").unwrap();
+    let actual = image::DynamicImage::ImageRgb8(luma.to_rgb8());
+    assert_image("tests/tmp/luma-reference.png", &actual, 1.0);
+}
+
+#[test]
+fn good_exact() {
+    let actual = image::io::Reader::open("tests/dog1.png").unwrap().decode().unwrap();
+    twenty_twenty::assert_image_exact("tests/dog1.png", &actual);
+}
+
+#[test]
+#[should_panic(expected = "differs first at pixel (3, 5)")]
+fn bad_exact() {
+    let mut actual = image::io::Reader::open("tests/dog1.png")
+        .unwrap()
+        .decode()
+        .unwrap()
+        .to_rgba8();
+    let pixel = actual.get_pixel_mut(3, 5);
+    pixel[0] = pixel[0].wrapping_add(1);
+    twenty_twenty::assert_image_exact("tests/dog1.png", &image::DynamicImage::ImageRgba8(actual));
+}
+
+#[test]
+fn alpha_channel() {
+    std::fs::create_dir_all("tests/tmp").unwrap();
+    let mask = |color: image::Rgba<u8>| {
+        image::DynamicImage::ImageRgba8(image::RgbaImage::from_fn(16, 16, |x, _| {
+            let mut pixel = color;
+            pixel[3] = if x < 8 { 255 } else { 0 };
+            pixel
+        }))
+    };
+    mask(image::Rgba([255, 0, 0, 0]))
+        .save("tests/tmp/alpha-channel.png")
+        .unwrap();
+
+    twenty_twenty::assert_image_channel(
+        "tests/tmp/alpha-channel.png",
+        &mask(image::Rgba([0, 0, 255, 0])),
+        1.0,
+        twenty_twenty::Channel::Alpha,
+    );
+}
+
+#[test]
+#[should_panic]
+fn red_channel() {
+    std::fs::create_dir_all("tests/tmp").unwrap();
+    let solid = |color: image::Rgba<u8>| image::DynamicImage::ImageRgba8(image::RgbaImage::from_pixel(16, 16, color));
+    solid(image::Rgba([255, 0, 0, 255]))
+        .save("tests/tmp/red-channel.png")
+        .unwrap();
+
+    twenty_twenty::assert_image_channel(
+        "tests/tmp/red-channel.png",
+        &solid(image::Rgba([0, 0, 255, 255])),
+        0.9,
+        twenty_twenty::Channel::Red,
+    );
+}
+
+#[test]
+fn ignore_border() {
+    let mut actual = image::io::Reader::open("tests/dog1.png")
+        .unwrap()
+        .decode()
+        .unwrap()
+        .to_rgba8();
+    for x in 0..actual.width() {
+        actual.put_pixel(x, 0, image::Rgba([255, 0, 255, 255]));
+    }
+    twenty_twenty::Config::new().ignore_border(1).assert_image(
+        "tests/dog1.png",
+        &image::DynamicImage::ImageRgba8(actual),
+        1.0,
+    );
+}
+
+#[test]
+#[should_panic(expected = "cannot ignore a border")]
+fn ignore_border_too_large() {
+    let actual = image::io::Reader::open("tests/dog1.png").unwrap().decode().unwrap();
+    twenty_twenty::Config::new()
+        .ignore_border(100)
+        .assert_image("tests/dog1.png", &actual, 1.0);
+}
+
+#[test]
+#[should_panic(expected = "largest difference around region x=96..112, y=96..112")]
+fn worst_region() {
+    assert_image("tests/dog1.png", &changed_dog(96, 96), 0.999);
+}
+
+#[test]
+#[should_panic(expected = "largest difference around region x=121..137, y=121..137")]
+fn worst_region_after_autocrop() {
+    std::fs::create_dir_all("tests/tmp").unwrap();
+    let dog = image::io::Reader::open("tests/dog1.png")
+        .unwrap()
+        .decode()
+        .unwrap()
+        .to_rgba8();
+    let padded = |dog: &image::RgbaImage, padding: u32| {
+        let mut image = image::RgbaImage::from_pixel(
+            dog.width() + padding * 2,
+            dog.height() + padding * 2,
+            image::Rgba([1, 2, 3, 4]),
+        );
+        image::imageops::replace(&mut image, dog, padding as i64, padding as i64);
+        image::DynamicImage::ImageRgba8(image)
+    };
+    padded(&dog, 10).save("tests/tmp/worst-region-autocrop.png").unwrap();
+    twenty_twenty::Config::new()
+        .autocrop(image::Rgba([1, 2, 3, 4]))
+        .assert_image(
+            "tests/tmp/worst-region-autocrop.png",
+            &padded(&changed_dog(96, 96).to_rgba8(), 25),
+            0.999,
+        );
+}
+
+#[test]
+fn edge_tolerance() {
+    std::fs::create_dir_all("tests/tmp").unwrap();
+    let line = |column: u32| {
+        image::DynamicImage::ImageRgba8(image::RgbaImage::from_fn(64, 64, |x, _| {
+            if x == column {
+                image::Rgba([0, 0, 0, 255])
+            } else {
+                image::Rgba([255, 255, 255, 255])
+            }
+        }))
+    };
+    line(30).save("tests/tmp/edge-tolerance.png").unwrap();
+    twenty_twenty::Config::new()
+        .edge_tolerance(1)
+        .assert_image("tests/tmp/edge-tolerance.png", &line(31), 1.0);
+}
+
+#[test]
+fn denoise_isolated() {
+    let mut actual = image::io::Reader::open("tests/dog1.png")
+        .unwrap()
+        .decode()
+        .unwrap()
+        .to_rgba8();
+    for (x, y) in [(10, 10), (50, 120), (150, 30), (199, 199)] {
+        actual.put_pixel(x, y, image::Rgba([255, 0, 255, 255]));
+    }
+    twenty_twenty::Config::new().denoise_isolated(1).assert_image(
+        "tests/dog1.png",
+        &image::DynamicImage::ImageRgba8(actual),
+        1.0,
+    );
+}
+
+#[test]
+fn compare_against_head() {
+    let actual = image::io::Reader::open("tests/dog1.png").unwrap().decode().unwrap();
+    twenty_twenty::Config::new()
+        .compare_against_head(true)
+        .assert_image("tests/dog1.png", &actual, 1.0);
+}
+
+/// dog1 with a black square in its transparent top left corner, and an importance map weighting
+/// that square by `square` and the rest of the image by `rest`.
+fn weighted_change(square: u8, rest: u8) -> (image::DynamicImage, image::GrayImage) {
+    let actual = changed_dog(0, 0);
+    let importance_map = image::GrayImage::from_fn(actual.width(), actual.height(), |x, y| {
+        image::Luma([if x < 16 && y < 16 { square } else { rest }])
+    });
+    (actual, importance_map)
+}
+
+#[test]
+fn weighted() {
+    let (actual, importance_map) = weighted_change(1, 255);
+    twenty_twenty::assert_image_weighted("tests/dog1.png", &actual, 0.999, &importance_map);
+}
+
+#[test]
+#[should_panic(expected = "less than min_permissible_similarity")]
+fn weighted_important_change() {
+    let (actual, importance_map) = weighted_change(255, 1);
+    twenty_twenty::assert_image_weighted("tests/dog1.png", &actual, 0.999, &importance_map);
+}
+
+#[test]
+fn weighted_uniformly() {
+    let (actual, importance_map) = weighted_change(255, 255);
+    let unweighted = twenty_twenty::assert_image_scored("tests/dog1.png", &actual, 0.0);
+    let weighted = twenty_twenty::Config::new()
+        .importance_map(importance_map)
+        .assert_image_scored("tests/dog1.png", &actual, 0.0);
+    assert_eq!(weighted, unweighted);
+}
+
+/// Save a flat dark gray reference at `path` and return a checkerboard of darker and lighter
+/// grays with the same mean in sRGB, which all round to the same value in linear light.
+fn dark_checkerboard(path: &str) -> image::DynamicImage {
+    std::fs::create_dir_all("tests/tmp").unwrap();
+    image::RgbaImage::from_pixel(64, 64, image::Rgba([13, 13, 13, 255]))
+        .save(path)
+        .unwrap();
+    image::DynamicImage::ImageRgba8(image::RgbaImage::from_fn(64, 64, |x, y| {
+        let value = if (x + y) % 2 == 0 { 7 } else { 19 };
+        image::Rgba([value, value, value, 255])
+    }))
+}
+
+#[test]
+fn auto_align() {
+    let expected = image::io::Reader::open("tests/dog1.png")
+        .unwrap()
+        .decode()
+        .unwrap()
+        .to_rgba8();
+    let actual = image::RgbaImage::from_fn(expected.width(), expected.height(), |x, y| {
+        *expected.get_pixel(x.saturating_sub(1), y)
+    });
+    twenty_twenty::Config::new().auto_align(2).assert_image(
+        "tests/dog1.png",
+        &image::DynamicImage::ImageRgba8(actual),
+        1.0,
+    );
+}
+
+#[test]
+#[should_panic(expected = "after aligning the actual image by shifting it (1, 0) pixels")]
+fn auto_align_reports_shift() {
+    let expected = image::io::Reader::open("tests/dog1.png")
+        .unwrap()
+        .decode()
+        .unwrap()
+        .to_rgba8();
+    let actual = image::RgbaImage::from_fn(expected.width(), expected.height(), |x, y| {
+        if (90..110).contains(&x) && (90..110).contains(&y) {
+            image::Rgba([0, 0, 0, 255])
+        } else {
+            *expected.get_pixel(x.saturating_sub(1), y)
+        }
+    });
+    twenty_twenty::Config::new().auto_align(2).assert_image(
+        "tests/dog1.png",
+        &image::DynamicImage::ImageRgba8(actual),
+        0.999,
+    );
+}
+
+#[test]
+fn autocrop() {
+    std::fs::create_dir_all("tests/tmp").unwrap();
+    let dog = image::io::Reader::open("tests/dog1.png")
+        .unwrap()
+        .decode()
+        .unwrap()
+        .to_rgba8();
+    let padded = |padding: u32| {
+        let mut image = image::RgbaImage::from_pixel(
+            dog.width() + padding * 2,
+            dog.height() + padding * 2,
+            image::Rgba([1, 2, 3, 4]),
+        );
+        image::imageops::replace(&mut image, &dog, padding as i64, padding as i64);
+        image::DynamicImage::ImageRgba8(image)
+    };
+    padded(10).save("tests/tmp/autocrop.png").unwrap();
+    twenty_twenty::Config::new()
+        .autocrop(image::Rgba([1, 2, 3, 4]))
+        .assert_image("tests/tmp/autocrop.png", &padded(25), 1.0);
+}
+
+#[test]
+#[should_panic(expected = "the actual image is entirely background")]
+fn autocrop_entirely_background() {
+    let actual = image::DynamicImage::ImageRgba8(image::RgbaImage::from_pixel(200, 200, image::Rgba([1, 2, 3, 4])));
+    twenty_twenty::Config::new()
+        .autocrop(image::Rgba([1, 2, 3, 4]))
+        .assert_image("tests/dog1.png", &actual, 1.0);
+}
+
+#[test]
+fn expected_scale() {
+    let expected = image::io::Reader::open("tests/dog1.png").unwrap().decode().unwrap();
+    let actual = expected.resize_exact(
+        expected.width() * 2,
+        expected.height() * 2,
+        image::imageops::FilterType::Lanczos3,
+    );
+    twenty_twenty::Config::new()
+        .expected_scale(2.0)
+        .assert_image("tests/dog1.png", &actual, 0.9);
+}
+
+#[test]
+#[should_panic(expected = "at a scale of 3")]
+fn expected_scale_mismatch() {
+    let dog = image::io::Reader::open("tests/dog1.png").unwrap().decode().unwrap();
+    let actual = dog.resize_exact(dog.width() * 2, dog.height() * 2, image::imageops::FilterType::Nearest);
+    twenty_twenty::Config::new()
+        .expected_scale(3.0)
+        .assert_image("tests/dog1.png", &actual, 0.9);
+}
+
+#[test]
+fn validate_reference() {
+    let (width, height) = image::image_dimensions("tests/dog1.png").unwrap();
+    assert_eq!(
+        twenty_twenty::validate_reference("tests/dog1.png").unwrap(),
+        (width, height)
+    );
+    assert!(twenty_twenty::validate_reference("tests/does-not-exist.png").is_err());
+}
+
+#[test]
+fn sidecar() {
+    std::fs::create_dir_all("tests/tmp").unwrap();
+    std::fs::copy("tests/dog1.png", "tests/tmp/sidecar.png").unwrap();
+    std::fs::write("tests/tmp/sidecar.png.tt.toml", "min = 0.5\n").unwrap();
+    assert_image("tests/tmp/sidecar.png", &changed_dog(0, 0), 1.0);
+}
+
+#[test]
+fn sidecar_mask_regions() {
+    std::fs::create_dir_all("tests/tmp").unwrap();
+    std::fs::copy("tests/dog1.png", "tests/tmp/sidecar-mask-regions.png").unwrap();
+    std::fs::write(
+        "tests/tmp/sidecar-mask-regions.png.tt.toml",
+        "mask_regions = [{ x = 0, y = 0, width = 16, height = 16 }, { x = 150, y = 150, width = 100, height = 100 }]\n",
+    )
+    .unwrap();
+    let mut actual = changed_dog(0, 0).to_rgba8();
+    paint_black_square(&mut actual, 180, 180);
+    assert_image(
+        "tests/tmp/sidecar-mask-regions.png",
+        &image::DynamicImage::ImageRgba8(actual),
+        1.0,
+    );
+}
+
+#[test]
+fn strip_letterbox() {
+    // Unlike the dog, a gradient has no flat edges of its own to be mistaken for letterboxing.
+    std::fs::create_dir_all("tests/tmp").unwrap();
+    let gradient = image::RgbaImage::from_fn(64, 48, |x, y| image::Rgba([(x * 4) as u8, (y * 5) as u8, 128, 255]));
+    gradient.save("tests/tmp/strip-letterbox.png").unwrap();
+    let mut letterboxed = image::RgbaImage::from_pixel(64, 68, image::Rgba([0, 0, 0, 255]));
+    image::imageops::replace(&mut letterboxed, &gradient, 0, 10);
+    twenty_twenty::Config::new().strip_letterbox(true).assert_image(
+        "tests/tmp/strip-letterbox.png",
+        &image::DynamicImage::ImageRgba8(letterboxed),
+        1.0,
+    );
+}
+
+#[test]
+fn unfocused_small_change() {
+    assert_image("tests/dog1.png", &changed_dog(96, 96), 0.99);
+}
+
+#[test]
+#[should_panic(expected = "less than min_permissible_similarity")]
+fn focus_on_changes() {
+    twenty_twenty::Config::new()
+        .focus_on_changes(true)
+        .assert_image("tests/dog1.png", &changed_dog(96, 96), 0.99);
+}
+
+#[test]
+fn focus_on_no_changes() {
+    let actual = image::io::Reader::open("tests/dog1.png").unwrap().decode().unwrap();
+    twenty_twenty::Config::new()
+        .focus_on_changes(true)
+        .assert_image("tests/dog1.png", &actual, 1.0);
+}
+
+#[test]
+fn indexed_reference() {
+    std::fs::create_dir_all("tests/tmp").unwrap();
+    // A checkerboard of four palette entries, one of them translucent.
+    let palette = [255, 0, 0, 0, 255, 0, 0, 0, 255, 255, 255, 255];
+    let trns = [255, 255, 255, 128];
+    let index = |x: u32, y: u32| ((x / 8 + y / 8) % 4) as usize;
+    let indices: Vec<u8> = (0..64 * 64).map(|i| index(i % 64, i / 64) as u8).collect();
+    let file = std::io::BufWriter::new(std::fs::File::create("tests/tmp/indexed.png").unwrap());
+    let mut encoder = png::Encoder::new(file, 64, 64);
+    encoder.set_color(png::ColorType::Indexed);
+    encoder.set_depth(png::BitDepth::Eight);
+    encoder.set_palette(palette.to_vec());
+    encoder.set_trns(trns.to_vec());
+    let mut writer = encoder.write_header().unwrap();
+    writer.write_image_data(&indices).unwrap();
+    writer.finish().unwrap();
+
+    // The palette expands to exactly the truecolor image.
+    let truecolor = image::DynamicImage::ImageRgba8(image::RgbaImage::from_fn(64, 64, |x, y| {
+        let i = index(x, y);
+        image::Rgba([palette[i * 3], palette[i * 3 + 1], palette[i * 3 + 2], trns[i]])
+    }));
+    let indexed = image::open("tests/tmp/indexed.png").unwrap();
+    assert_eq!(twenty_twenty::compare_images(&indexed, &truecolor).unwrap(), 1.0);
+    assert_image("tests/tmp/indexed.png", &truecolor, 1.0);
+}
+
+#[test]
+fn gray_alpha_reference() {
+    std::fs::create_dir_all("tests/tmp").unwrap();
+    let gray = image::DynamicImage::ImageLumaA8(image::GrayAlphaImage::from_fn(64, 64, |x, y| {
+        image::LumaA([(x * 4) as u8, (y * 4) as u8])
+    }));
+    gray.save("tests/tmp/gray-alpha.png").unwrap();
+
+    // The gray is spread across the color channels and the alpha is kept.
+    let truecolor = image::DynamicImage::ImageRgba8(gray.to_rgba8());
+    assert_eq!(truecolor.to_rgba8().get_pixel(8, 16).0, [32, 32, 32, 64]);
+    let reference = image::open("tests/tmp/gray-alpha.png").unwrap();
+    assert_eq!(twenty_twenty::compare_images(&reference, &truecolor).unwrap(), 1.0);
+    assert_image("tests/tmp/gray-alpha.png", &truecolor, 1.0);
+}
+
+#[test]
+fn assert_image_scored() {
+    let actual = image::io::Reader::open("tests/dog1.png").unwrap().decode().unwrap();
+    let score = twenty_twenty::assert_image_scored("tests/dog1.png", &actual, 0.9);
+    assert_eq!(score, 1.0);
+}
+
+#[test]
+fn try_assert_image() {
+    let actual = image::io::Reader::open("tests/dog1.png").unwrap().decode().unwrap();
+    assert_eq!(
+        twenty_twenty::try_assert_image("tests/dog1.png", &actual, 0.9).unwrap(),
+        1.0
+    );
+
+    let missing =
+        twenty_twenty::Config::new()
+            .require_reference(true)
+            .try_assert_image("tests/tmp/missing.png", &actual, 0.0);
+    assert!(missing.unwrap_err().to_string().contains("does not exist"));
+
+    std::fs::create_dir_all("tests/tmp").unwrap();
+    std::fs::write("tests/tmp/corrupt.png", b"not a png").unwrap();
+    assert!(twenty_twenty::try_assert_image("tests/tmp/corrupt.png", &actual, 0.0).is_err());
+
+    assert!(twenty_twenty::try_assert_image("tests/dog2.png", &actual, 0.0).is_err());
+
+    let other = image::io::Reader::open("tests/dog2.png").unwrap().decode().unwrap();
+    let other = other.resize_exact(200, 200, image::imageops::FilterType::Triangle);
+    assert!(twenty_twenty::try_assert_image("tests/dog1.png", &other, 1.0).is_err());
+
+    assert!(twenty_twenty::try_assert_rgba8("tests/dog1.png", 200, 200, &[0; 4], 0.0).is_err());
+}
+
+#[test]
+fn diff_references() {
+    let comparison = twenty_twenty::diff_references("tests/dog1.png", "tests/dog1.png").unwrap();
+    assert_eq!(comparison.score, 1.0);
+    assert_eq!(comparison.expected.to_rgba8(), comparison.actual.to_rgba8());
+    assert_eq!(comparison.diff.unwrap().dimensions(), (600, 200));
+
+    assert!(twenty_twenty::diff_references("tests/dog1.png", "tests/dog2.png").is_err());
+    assert!(twenty_twenty::diff_references("tests/dog1.png", "tests/tmp/missing.png").is_err());
+}
+
+#[test]
+fn thresholds() {
+    let actual = image::io::Reader::open("tests/dog1.png").unwrap().decode().unwrap();
+    // Passes with a warning, as the score of 1 is below the warning threshold.
+    twenty_twenty::Config::new()
+        .thresholds(0.9, 1.1)
+        .assert_image("tests/dog1.png", &actual, 1.0);
+}
+
+#[test]
+#[should_panic(expected = "is less than the failure threshold")]
+fn thresholds_out_of_order() {
+    let actual = image::io::Reader::open("tests/dog1.png").unwrap().decode().unwrap();
+    twenty_twenty::Config::new()
+        .thresholds(0.9, 0.5)
+        .assert_image("tests/dog1.png", &actual, 0.9);
+}
+
+#[test]
+fn allow_rotations() {
+    let actual = image::io::Reader::open("tests/dog1.png").unwrap().decode().unwrap();
+    twenty_twenty::Config::new()
+        .allow_rotations(true)
+        .assert_image("tests/dog1.png", &actual.rotate270(), 0.99);
+}
+
+#[test]
+#[should_panic]
+fn rotated_without_allow_rotations() {
+    let actual = image::io::Reader::open("tests/dog1.png").unwrap().decode().unwrap();
+    twenty_twenty::assert_image("tests/dog1.png", &actual.rotate270(), 0.99);
+}
+
+#[test]
+fn assert_roundtrip() {
+    let original = image::io::Reader::open("tests/dog1.png").unwrap().decode().unwrap();
+    let mut encoded = std::io::Cursor::new(Vec::new());
+    original.write_to(&mut encoded, image::ImageFormat::Png).unwrap();
+    let roundtripped = image::load_from_memory(encoded.get_ref()).unwrap();
+    assert_eq!(twenty_twenty::assert_roundtrip(&original, &roundtripped, 0.99), 1.0);
+}
+
+#[test]
+#[should_panic(expected = "the round-tripped image is 100x100 but the original is 200x200")]
+fn assert_roundtrip_resized() {
+    let original = image::io::Reader::open("tests/dog1.png").unwrap().decode().unwrap();
+    twenty_twenty::assert_roundtrip(&original, &original.thumbnail(100, 100), 0.0);
+}
+
+#[test]
+fn assert_image_against_fn() {
+    let checkerboard = |x: u32, y: u32| {
+        let value = if (x / 8 + y / 8).is_multiple_of(2) { 0 } else { 255 };
+        image::Rgba([value, value, value, 255])
+    };
+    let actual = image::DynamicImage::ImageRgba8(image::RgbaImage::from_fn(64, 64, checkerboard));
+    twenty_twenty::assert_image_against_fn(checkerboard, &actual, 1.0);
+}
+
+#[test]
+#[should_panic(expected = "image doesn't match its generated reference, score is")]
+fn assert_image_against_wrong_fn() {
+    let actual = image::io::Reader::open("tests/dog1.png").unwrap().decode().unwrap();
+    twenty_twenty::assert_image_against_fn(|_, _| image::Rgba([255, 0, 0, 255]), &actual, 0.9);
+}
+
+#[test]
+#[should_panic(expected = "is grossly different")]
+fn fast_fail() {
+    let white = image::RgbaImage::from_pixel(64, 64, image::Rgba([255, 255, 255, 255]));
+    std::fs::create_dir_all("tests/tmp").unwrap();
+    white.save("tests/tmp/fast-fail.png").unwrap();
+    let black = image::RgbaImage::from_pixel(64, 64, image::Rgba([0, 0, 0, 255]));
+    twenty_twenty::Config::new()
+        .fast_fail(true)
+        .assert_image("tests/tmp/fast-fail.png", &black.into(), 0.9);
+}
+
+#[test]
+fn fast_fail_masked() {
+    let white = image::RgbaImage::from_pixel(64, 64, image::Rgba([255, 255, 255, 255]));
+    std::fs::create_dir_all("tests/tmp").unwrap();
+    white.save("tests/tmp/fast-fail-masked.png").unwrap();
+    // Mostly black, but only where it is masked, so it matches once the mask is applied.
+    let mut actual = white.clone();
+    for (_, y, pixel) in actual.enumerate_pixels_mut() {
+        if y < 48 {
+            *pixel = image::Rgba([0, 0, 0, 255]);
+        }
+    }
+    twenty_twenty::Config::new()
+        .fast_fail(true)
+        .mask_region(twenty_twenty::Rect {
+            x: 0,
+            y: 0,
+            width: 64,
+            height: 48,
+        })
+        .assert_image("tests/tmp/fast-fail-masked.png", &actual.into(), 0.9);
+}
+
+#[test]
+fn reference_alpha_as_mask() {
+    let dog = image::io::Reader::open("tests/dog1.png")
+        .unwrap()
+        .decode()
+        .unwrap()
+        .to_rgba8();
+    // Only the left half of the reference matters.
+    let mut reference = dog.clone();
+    for (x, _, pixel) in reference.enumerate_pixels_mut() {
+        pixel[3] = if x < 100 { 255 } else { 0 };
+    }
+    std::fs::create_dir_all("tests/tmp").unwrap();
+    reference.save("tests/tmp/alpha-mask.png").unwrap();
+
+    let mut actual = dog.clone();
+    for (x, _, pixel) in actual.enumerate_pixels_mut() {
+        pixel[3] = 255;
+        if x >= 100 {
+            *pixel = image::Rgba([255, 0, 0, 255]);
+        }
+    }
+    twenty_twenty::Config::new().reference_alpha_as_mask(true).assert_image(
+        "tests/tmp/alpha-mask.png",
+        &actual.into(),
+        0.99,
+    );
+}
+
+#[test]
+fn composite_over() {
+    let mut reference = image::io::Reader::open("tests/dog1.png")
+        .unwrap()
+        .decode()
+        .unwrap()
+        .to_rgba8();
+    for pixel in reference.pixels_mut() {
+        pixel[3] = 128;
+    }
+    std::fs::create_dir_all("tests/tmp").unwrap();
+    reference.save("tests/tmp/composite-over.png").unwrap();
+
+    let mut actual = reference.clone();
+    for pixel in actual.pixels_mut() {
+        for channel in &mut pixel.0[..3] {
+            *channel = ((*channel as u16 * 128 + 255 * 127) / 255) as u8;
+        }
+        pixel[3] = 255;
+    }
+    twenty_twenty::Config::new()
+        .composite_over(image::Rgb([255, 255, 255]))
+        .assert_image("tests/tmp/composite-over.png", &actual.into(), 0.99);
+}
+
+#[test]
+fn compare_bit_depth() {
+    let actual = image::io::Reader::open("tests/dog1.png").unwrap().decode().unwrap();
+    let actual = image::DynamicImage::ImageRgba16(actual.to_rgba16());
+    twenty_twenty::Config::new()
+        .compare_bit_depth(twenty_twenty::BitDepth::Sixteen)
+        .assert_image("tests/dog1.png", &actual, 1.0);
+    twenty_twenty::Config::new()
+        .compare_bit_depth(twenty_twenty::BitDepth::MatchReference)
+        .assert_image("tests/dog1.png", &actual, 1.0);
+}
+
+#[test]
+#[should_panic(expected = "can't be applied when comparing at 16 bits per channel")]
+fn compare_bit_depth_with_ignore_border() {
+    let actual = image::io::Reader::open("tests/dog1.png").unwrap().decode().unwrap();
+    twenty_twenty::Config::new()
+        .compare_bit_depth(twenty_twenty::BitDepth::Sixteen)
+        .ignore_border(8)
+        .assert_image("tests/dog1.png", &actual, 0.0);
+}
+
+#[test]
+fn self_symmetry() {
+    let dog = image::io::Reader::open("tests/dog1.png").unwrap().decode().unwrap();
+    let mut mirrored = image::RgbaImage::new(dog.width() * 2, dog.height());
+    image::imageops::replace(&mut mirrored, &dog.to_rgba8(), 0, 0);
+    image::imageops::replace(&mut mirrored, &dog.fliph().to_rgba8(), dog.width() as i64, 0);
+    let mirrored = image::DynamicImage::ImageRgba8(mirrored);
+    twenty_twenty::assert_self_symmetry(&mirrored, twenty_twenty::Axis::Vertical, 1.0);
+}
+
+#[test]
+#[should_panic(expected = "isn't symmetric about its horizontal axis")]
+fn not_self_symmetric() {
+    let dog = image::io::Reader::open("tests/dog1.png").unwrap().decode().unwrap();
+    twenty_twenty::assert_self_symmetry(&dog, twenty_twenty::Axis::Horizontal, 0.99);
+}
+
+#[test]
+fn linearize() {
+    let actual = dark_checkerboard("tests/tmp/linearize.png");
+    twenty_twenty::Config::new()
+        .linearize(true)
+        .assert_image("tests/tmp/linearize.png", &actual, 0.9);
+}
+
+#[test]
+#[should_panic(expected = "less than min_permissible_similarity")]
+fn not_linearized() {
+    let actual = dark_checkerboard("tests/tmp/not-linearized.png");
+    twenty_twenty::assert_image("tests/tmp/not-linearized.png", &actual, 0.9);
+}
+
+/// Save a flat brand red at `path` and return it with its hue shifted slightly towards magenta,
+/// which barely changes any of its sRGB channels.
+fn hue_shifted_brand_color(path: &str) -> image::DynamicImage {
+    std::fs::create_dir_all("tests/tmp").unwrap();
+    image::RgbaImage::from_pixel(32, 32, image::Rgba([200, 50, 50, 255]))
+        .save(path)
+        .unwrap();
+    image::DynamicImage::ImageRgba8(image::RgbaImage::from_pixel(32, 32, image::Rgba([200, 50, 55, 255])))
+}
+
+#[test]
+fn srgb_hue_shift() {
+    let actual = hue_shifted_brand_color("tests/tmp/srgb-hue-shift.png");
+    assert_image("tests/tmp/srgb-hue-shift.png", &actual, 0.99);
+}
+
+#[test]
+#[should_panic(expected = "less than min_permissible_similarity")]
+fn lab_hue_shift() {
+    let actual = hue_shifted_brand_color("tests/tmp/lab-hue-shift.png");
+    twenty_twenty::Config::new()
+        .color_space(twenty_twenty::ColorSpace::Lab)
+        .assert_image("tests/tmp/lab-hue-shift.png", &actual, 0.99);
+}
+
+#[test]
+fn exr() {
+    std::fs::create_dir_all("tests/tmp").unwrap();
+    let expected =
+        image::Rgba32FImage::from_fn(32, 32, |x, y| image::Rgba([x as f32 * 4.0, y as f32 * 0.5, 10.0, 1.0]));
+    image::DynamicImage::ImageRgba32F(expected.clone())
+        .save_with_format("tests/tmp/hdr.exr", image::ImageFormat::OpenExr)
+        .unwrap();
+    twenty_twenty::assert_exr("tests/tmp/hdr.exr", &expected, 1.0);
+    twenty_twenty::Config::new()
+        .tonemap(true)
+        .assert_exr("tests/tmp/hdr.exr", &expected, 1.0);
+}
+
+#[test]
+fn prescreen() {
+    let actual = image::io::Reader::open("tests/initial-grid.png")
+        .unwrap()
+        .decode()
+        .unwrap();
+    twenty_twenty::Config::new()
+        .prescreen(256)
+        .assert_image("tests/initial-grid.png", &actual, 0.999);
+}
+
+#[test]
+fn good_svg() {
+    let actual = image::RgbaImage::from_fn(128, 128, |x, y| {
+        if (32..96).contains(&x) && (32..96).contains(&y) {
+            image::Rgba([255, 0, 0, 255])
+        } else {
+            image::Rgba([255, 255, 255, 255])
+        }
+    });
+    twenty_twenty::assert_image_against_svg("tests/square.svg", &image::DynamicImage::ImageRgba8(actual), 0.99);
+}
+
+#[tokio::test]
+async fn good_async() {
+    let actual = image::io::Reader::open("tests/dog1.png").unwrap().decode().unwrap();
+    twenty_twenty::assert_image_async("tests/dog1.png", &actual, 1.0).await;
+}
+
 #[test]
 fn good_h264() {
     let actual = std::fs::read("tests/initial-grid.h264").unwrap();
     assert_h264_frame("tests/initial-grid.png", &actual, 0.999);
 }
 
+#[test]
+fn good_h264_luma() {
+    let reference = image::io::Reader::open("tests/initial-grid.png")
+        .unwrap()
+        .decode()
+        .unwrap()
+        .to_luma8();
+    std::fs::create_dir_all("tests/tmp").unwrap();
+    reference.save("tests/tmp/initial-grid-luma.png").unwrap();
+    let actual = std::fs::read("tests/initial-grid.h264").unwrap();
+    twenty_twenty::Config::new()
+        .plane(twenty_twenty::Plane::Luma)
+        .assert_h264_frame("tests/tmp/initial-grid-luma.png", &actual, 0.99);
+}
+
+#[test]
+fn h264_skip_frames() {
+    let actual = std::fs::read("tests/multiple-frames.h264").unwrap();
+    let frames = twenty_twenty::h264_frames_to_pngs(&actual, "tests/tmp/skip-frames").unwrap();
+    assert!(frames > 1);
+    twenty_twenty::Config::new()
+        .skip_frames(1)
+        .assert_h264_frame("tests/tmp/skip-frames/frame-1.png", &actual, 0.999);
+}
+
+#[test]
+fn h264_frame_from_many_packets() {
+    // Each frame of the clip spans several NAL units, which have to reach the decoder as the
+    // demuxer splits them up to decode the first frame whole.
+    let actual = std::fs::read("tests/multiple-frames.h264").unwrap();
+    let first = twenty_twenty::decode_h264_frame(&actual).unwrap();
+    let frames = twenty_twenty::decode_h264_frames(&actual).unwrap();
+    assert_eq!(twenty_twenty::compare_images(&frames[0], &first).unwrap(), 1.0);
+}
+
+#[test]
+fn good_h264_reader() {
+    let actual = std::fs::File::open("tests/initial-grid.h264").unwrap();
+    twenty_twenty::assert_h264_frame_reader("tests/initial-grid.png", actual, 0.999);
+}
+
+#[test]
+fn video_file_frame() {
+    twenty_twenty::assert_video_file_frame("tests/multiple-frames.h264", "tests/multiple-frames.png", 0.999, 0);
+    assert!(twenty_twenty::decode_video_file_frame("tests/multiple-frames.h264", 100_000).is_err());
+}
+
+#[test]
+fn video_file_frame_mp4() {
+    twenty_twenty::assert_video_file_frame("tests/multiple-frames.mp4", "tests/multiple-frames.png", 0.999, 0);
+
+    // The clip holds the first nine frames of the raw H.264 one, muxed into an MP4.
+    let frames = twenty_twenty::decode_h264_frames(&std::fs::read("tests/multiple-frames.h264").unwrap()).unwrap();
+    let frame = twenty_twenty::decode_video_file_frame("tests/multiple-frames.mp4", 8).unwrap();
+    assert_eq!(frame.to_rgba8(), frames[8].to_rgba8());
+    assert!(twenty_twenty::decode_video_file_frame("tests/multiple-frames.mp4", 9).is_err());
+}
+
 #[test]
 fn good_h264_multiple_frames() {
     let actual = std::fs::read("tests/multiple-frames.h264").unwrap();
     assert_h264_frame("tests/multiple-frames.png", &actual, 0.999);
 }
 
+#[test]
+fn decode_h264() {
+    let actual = std::fs::read("tests/initial-grid.h264").unwrap();
+    let frame = twenty_twenty::decode_h264_frame(&actual).unwrap();
+    assert_image("tests/initial-grid.png", &frame, 0.999);
+    let frames = twenty_twenty::decode_h264_frames(&actual).unwrap();
+    assert!(!frames.is_empty());
+}
+
+#[test]
+fn h264_frames() {
+    let actual = std::fs::read("tests/multiple-frames.h264").unwrap();
+    let count = twenty_twenty::h264_frames_to_pngs(&actual, "tests/tmp/h264-frames").unwrap();
+    twenty_twenty::assert_h264_frames("tests/tmp/h264-frames/frame-{}.png", &actual, &vec![0.999; count]);
+}
+
+#[test]
+fn h264_frame_to_png() {
+    let actual = std::fs::read("tests/initial-grid.h264").unwrap();
+    twenty_twenty::h264_frame_to_png(&actual, "tests/tmp/h264-frame.png").unwrap();
+    assert_image(
+        "tests/initial-grid.png",
+        &image::open("tests/tmp/h264-frame.png").unwrap(),
+        0.999,
+    );
+}
+
+#[test]
+fn h264_frame_to_png_despite_extension() {
+    let actual = std::fs::read("tests/initial-grid.h264").unwrap();
+    twenty_twenty::h264_frame_to_png(&actual, "tests/tmp/h264-frame.bmp").unwrap();
+    let format = image::guess_format(&std::fs::read("tests/tmp/h264-frame.bmp").unwrap()).unwrap();
+    assert_eq!(format, image::ImageFormat::Png);
+}
+
+#[test]
+#[should_panic(expected = "thresholds were given")]
+fn h264_frames_threshold_count() {
+    let actual = std::fs::read("tests/multiple-frames.h264").unwrap();
+    twenty_twenty::assert_h264_frames("tests/tmp/h264-frames/frame-{}.png", &actual, &[]);
+}
+
+#[test]
+fn compare_videos() {
+    let actual = std::fs::read("tests/multiple-frames.h264").unwrap();
+    let comparison = twenty_twenty::compare_videos(&actual, &actual).unwrap();
+    assert_eq!(comparison.mean, 1.0);
+    assert_eq!(comparison.worst, 1.0);
+}
+
 #[test]
 #[should_panic]
 fn bad_h264() {